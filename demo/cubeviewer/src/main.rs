@@ -2,13 +2,12 @@ use rubikmaster::*;
 use std::collections::HashSet;
 
 fn main() {
-    let mut command_list = vec![];
-    for c in crate::random(1_000_000) {
-        command_list.push(coord::rotation_of(c));
-    }
+    let command_stream =
+        component::CommandStream::new(random_iter(1_000_000).map(coord::rotation_of));
     yew::start_app_with_props::<component::Cube>(component::Props {
         init_state: matrix::PermutationMatrix::identity(),
-        command_list,
+        command_list: vec![],
+        command_stream: Some(command_stream),
         blacklist: HashSet::new(),
     });
 }
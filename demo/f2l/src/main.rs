@@ -161,7 +161,7 @@ fn make_problem(i: usize) -> Problem {
         no: i,
         state: init_state,
         solve: y_interpret(solve),
-        solve_seq: f2l.to_owned(),
+        solve_seq: format::format(&parser::parse(&f2l).unwrap().1, &format::Style::default()),
         ok: true,
     }
 }
@@ -0,0 +1,183 @@
+//! Collection of block building tools.
+//!
+//! A block is solved if its stickers match the centers.
+
+use crate::coord::{Surface, SURFACE_LIST};
+use crate::cubie::{corner_facelets, edge_facelets};
+use crate::matrix::PermutationMatrix;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+    B2x2x2,
+    B2x2x3,
+    F2lMinus1,
+}
+
+/// A location of a block. The pieces are in the order of `cubie`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Block {
+    /// The 2x2x2 block at the corner.
+    B2x2x2(usize),
+    /// The 2x2x3 block along the edge.
+    B2x2x3(usize),
+    /// The first two layers on the face without the slot of the corner.
+    F2lMinus1(Surface, usize),
+}
+
+fn opposite(face: u8) -> u8 {
+    face ^ 1
+}
+fn faces<const N: usize>(facelets: [u8; N]) -> [u8; N] {
+    facelets.map(|f| f / 9)
+}
+
+impl Block {
+    pub fn kind(self) -> BlockKind {
+        match self {
+            Block::B2x2x2(_) => BlockKind::B2x2x2,
+            Block::B2x2x3(_) => BlockKind::B2x2x3,
+            Block::F2lMinus1(..) => BlockKind::F2lMinus1,
+        }
+    }
+    /// All the locations of the kind: 8 2x2x2, 12 2x2x3 and 24 F2L-1 blocks.
+    pub fn all(kind: BlockKind) -> Vec<Block> {
+        match kind {
+            BlockKind::B2x2x2 => (0..8).map(Block::B2x2x2).collect(),
+            BlockKind::B2x2x3 => (0..12).map(Block::B2x2x3).collect(),
+            BlockKind::F2lMinus1 => SURFACE_LIST
+                .iter()
+                .flat_map(|&s| {
+                    (0..8)
+                        .filter(move |&i| faces(corner_facelets(i)).contains(&(s as u8)))
+                        .map(move |i| Block::F2lMinus1(s, i))
+                })
+                .collect(),
+        }
+    }
+    /// The faces the pieces must not touch.
+    fn forbidden(self) -> Vec<u8> {
+        match self {
+            Block::B2x2x2(i) => faces(corner_facelets(i)).map(opposite).to_vec(),
+            Block::B2x2x3(i) => faces(edge_facelets(i)).map(opposite).to_vec(),
+            Block::F2lMinus1(s, _) => vec![opposite(s as u8)],
+        }
+    }
+    /// The faces of the missing slot of F2L-1.
+    fn slot(self) -> Vec<u8> {
+        match self {
+            Block::F2lMinus1(s, i) => faces(corner_facelets(i))
+                .iter()
+                .copied()
+                .filter(|&f| f != s as u8)
+                .collect(),
+            _ => vec![],
+        }
+    }
+    pub fn corners(self) -> Vec<usize> {
+        let forbidden = self.forbidden();
+        (0..8)
+            .filter(|&i| {
+                faces(corner_facelets(i))
+                    .iter()
+                    .all(|f| !forbidden.contains(f))
+            })
+            .filter(|&i| !matches!(self, Block::F2lMinus1(_, c) if c == i))
+            .collect()
+    }
+    pub fn edges(self) -> Vec<usize> {
+        let (forbidden, slot) = (self.forbidden(), self.slot());
+        (0..12)
+            .filter(|&i| {
+                let fs = faces(edge_facelets(i));
+                fs.iter().all(|f| !forbidden.contains(f)) && !fs.iter().all(|f| slot.contains(f))
+            })
+            .collect()
+    }
+    /// The stickers of the pieces.
+    pub fn facelets(self) -> Vec<u8> {
+        let mut v: Vec<u8> = self
+            .corners()
+            .into_iter()
+            .flat_map(corner_facelets)
+            .collect();
+        v.extend(self.edges().into_iter().flat_map(edge_facelets));
+        v
+    }
+    /// Check if the block is solved.
+    pub fn solved(self, mat: &PermutationMatrix) -> bool {
+        self.facelets().into_iter().all(|f| {
+            let center = f / 9 * 9 + 4;
+            mat.inv_perm[f as usize] / 9 == mat.inv_perm[center as usize] / 9
+        })
+    }
+}
+
+/// The locations of the solved blocks of the kind.
+pub fn solved_blocks(mat: &PermutationMatrix, kind: BlockKind) -> Vec<Block> {
+    Block::all(kind)
+        .into_iter()
+        .filter(|b| b.solved(mat))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix;
+    use crate::parser::parse;
+    use crate::{cfop, flatten};
+
+    fn of(s: &str) -> PermutationMatrix {
+        matrix::of_seq(&flatten(parse(s).unwrap().1))
+    }
+    #[test]
+    fn test_pieces() {
+        for kind in [BlockKind::B2x2x2, BlockKind::B2x2x3, BlockKind::F2lMinus1] {
+            let n = match kind {
+                BlockKind::B2x2x2 => (8, 1, 3),
+                BlockKind::B2x2x3 => (12, 2, 5),
+                BlockKind::F2lMinus1 => (24, 3, 7),
+            };
+            let all = Block::all(kind);
+            assert_eq!(all.len(), n.0);
+            for b in all {
+                assert_eq!((b.corners().len(), b.edges().len()), (n.1, n.2), "{:?}", b);
+                assert!(b.solved(&PermutationMatrix::identity()));
+            }
+        }
+        // DBL with DL, DB and BL.
+        assert_eq!(Block::B2x2x2(6).edges(), vec![6, 7, 10]);
+        // DLF and DBL with DF, DL, DB, FL and BL.
+        assert_eq!(Block::B2x2x3(6).corners(), vec![5, 6]);
+        assert_eq!(Block::B2x2x3(6).edges(), vec![5, 6, 7, 9, 10]);
+        // Without DFR and FR.
+        let b = Block::F2lMinus1(Surface::D, 4);
+        assert_eq!(b.corners(), vec![5, 6, 7]);
+        assert_eq!(b.edges(), vec![4, 5, 6, 7, 9, 10, 11]);
+    }
+    #[test]
+    fn test_solved() {
+        let m = of("RUR'");
+        assert!(Block::F2lMinus1(Surface::D, 4).solved(&m));
+        assert!(!Block::F2lMinus1(Surface::D, 5).solved(&m));
+        assert_eq!(solved_blocks(&m, BlockKind::F2lMinus1).len(), 1);
+        // 2x2x2 blocks away from U and R.
+        let m = of("R U");
+        assert_eq!(
+            solved_blocks(&m, BlockKind::B2x2x2),
+            vec![Block::B2x2x2(5), Block::B2x2x2(6)]
+        );
+        assert_eq!(solved_blocks(&m, BlockKind::B2x2x3), vec![Block::B2x2x3(6)]);
+        // The blocks are judged by the centers.
+        let m = of("x y");
+        assert_eq!(solved_blocks(&m, BlockKind::F2lMinus1).len(), 24);
+        // The last layer doesn't matter.
+        let m = of("RUR'URU2R'");
+        let d: Vec<Block> = solved_blocks(&m, BlockKind::F2lMinus1)
+            .into_iter()
+            .filter(|b| matches!(b, Block::F2lMinus1(Surface::D, _)))
+            .collect();
+        assert_eq!(d.len(), 4);
+        assert!(cfop::f2l_solved(&m));
+    }
+}
@@ -2,18 +2,23 @@
 
 use crate::matrix;
 use crate::matrix::{same_color_check, PermutationMatrix};
-use crate::{Command, Move};
+use crate::solver::auf_command;
+use crate::Command;
+#[cfg(test)]
+use crate::Move;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
 
-const R: [u8; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
-const L: [u8; 9] = [9, 10, 11, 12, 13, 14, 15, 16, 17];
-const U: [u8; 9] = [18, 19, 20, 21, 22, 23, 24, 25, 26];
-const D: [u8; 9] = [27, 28, 29, 30, 31, 32, 33, 34, 35];
-const F: [u8; 9] = [36, 37, 38, 39, 40, 41, 42, 43, 44];
-const B: [u8; 9] = [45, 46, 47, 48, 49, 50, 51, 52, 53];
-const R2: [u8; 6] = [0, 1, 3, 4, 6, 7];
-const L2: [u8; 6] = [12, 13, 14, 15, 16, 17];
-const F2: [u8; 6] = [39, 40, 41, 42, 43, 44];
-const B2: [u8; 6] = [45, 46, 48, 49, 51, 52];
+pub(crate) const R: [u8; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+pub(crate) const L: [u8; 9] = [9, 10, 11, 12, 13, 14, 15, 16, 17];
+pub(crate) const U: [u8; 9] = [18, 19, 20, 21, 22, 23, 24, 25, 26];
+pub(crate) const D: [u8; 9] = [27, 28, 29, 30, 31, 32, 33, 34, 35];
+pub(crate) const F: [u8; 9] = [36, 37, 38, 39, 40, 41, 42, 43, 44];
+pub(crate) const B: [u8; 9] = [45, 46, 47, 48, 49, 50, 51, 52, 53];
+pub(crate) const R2: [u8; 6] = [0, 1, 3, 4, 6, 7];
+pub(crate) const L2: [u8; 6] = [12, 13, 14, 15, 16, 17];
+pub(crate) const F2: [u8; 6] = [39, 40, 41, 42, 43, 44];
+pub(crate) const B2: [u8; 6] = [45, 46, 48, 49, 51, 52];
 
 /// Check if the cube is solved.
 pub fn solved(mat: &PermutationMatrix) -> bool {
@@ -25,6 +30,14 @@ pub fn solved(mat: &PermutationMatrix) -> bool {
         && same_color_check(mat, L)
 }
 
+pub(crate) const D_CROSS: [u8; 5] = [28, 30, 31, 32, 34];
+pub(crate) const CROSS_SIDES: [[u8; 2]; 4] = [[3, 4], [43, 40], [16, 13], [48, 49]];
+
+/// Check if the cross on the D face is solved.
+pub fn cross_solved(mat: &PermutationMatrix) -> bool {
+    same_color_check(mat, D_CROSS) && CROSS_SIDES.iter().all(|&s| same_color_check(mat, s))
+}
+
 /// Check if the F2L is solved.
 pub fn f2l_solved(mat: &PermutationMatrix) -> bool {
     same_color_check(mat, D)
@@ -70,6 +83,20 @@ fn test_solved() {
     }
 }
 #[test]
+fn test_cross_solved() {
+    let mut m = PermutationMatrix::identity();
+    for mov in [Move::U, Move::y] {
+        m = matof(Command(mov, 1)) * m;
+        assert!(cross_solved(&m));
+    }
+    for mov in [Move::D, Move::F, Move::R, Move::M, Move::E, Move::x] {
+        let c = matof(Command(mov, 1));
+        assert!(!cross_solved(&(c * m)));
+    }
+    let c = matof(Command(Move::z, 2)) * matof(Command(Move::x, 2));
+    assert!(cross_solved(&(c * m)));
+}
+#[test]
 fn test_f2l_solved_ux() {
     let mut m = PermutationMatrix::identity();
     let u = matof(Command(Move::U, 1));
@@ -314,3 +341,131 @@ fn test_oll_list() {
         assert!(f2l_solved(&m));
     }
 }
+
+/// Stickers of the U layer except the center.
+fn ll_facelets() -> Vec<u8> {
+    let mut v = vec![];
+    for i in 0..4 {
+        v.extend(crate::cubie::corner_facelets(i));
+        v.extend(crate::cubie::edge_facelets(i));
+    }
+    v
+}
+/// Which stickers of the U layer have the color of the U center.
+fn oll_signature(mat: &PermutationMatrix) -> u32 {
+    let mut sig = 0;
+    for (i, &f) in ll_facelets().iter().enumerate() {
+        if same_color_check(mat, [U[4], f]) {
+            sig |= 1 << i;
+        }
+    }
+    sig
+}
+/// `U^pre`, the algorithm and then `U^post`.
+pub(crate) fn with_auf(seq: &str, pre: i8, post: i8) -> Vec<Command> {
+    let mut cs: Vec<Command> = auf_command(pre).into_iter().collect();
+    cs.extend(crate::flatten(crate::parser::parse(seq).unwrap().1));
+    cs.extend(auf_command(post));
+    cs
+}
+/// The state which is solved by `U^auf` followed by the algorithm.
+fn case_of(seq: &str, auf: i8) -> PermutationMatrix {
+    matrix::of_seq(&with_auf(seq, auf, 0)).inv()
+}
+static OLL_CASES: Lazy<HashMap<u32, (usize, i8)>> = Lazy::new(|| {
+    let mut tbl = HashMap::new();
+    for (i, seq) in OLL_LIST.iter().enumerate() {
+        for auf in 0..4 {
+            tbl.entry(oll_signature(&case_of(seq, auf)))
+                .or_insert((i, auf));
+        }
+    }
+    tbl
+});
+
+/// Recognize the OLL case.
+///
+/// Returns the index in `OLL_LIST` and the number of U turns to do before the algorithm.
+/// `None` if the F2L isn't solved or the OLL is already solved.
+pub fn recognize_oll(mat: &PermutationMatrix) -> Option<(usize, i8)> {
+    if !f2l_solved(mat) || oll_solved(mat) {
+        return None;
+    }
+    OLL_CASES.get(&oll_signature(mat)).copied()
+}
+#[test]
+fn test_recognize_oll() {
+    // Every orientation of the last layer but the solved one.
+    assert_eq!(OLL_CASES.len(), 27 * 8 - 1);
+    assert_eq!(recognize_oll(&PermutationMatrix::identity()), None);
+    for (i, seq) in OLL_LIST.iter().enumerate() {
+        for auf in 0..4 {
+            // The permutation of the last layer shouldn't matter.
+            let (_, pll) = PLL_LIST[(i + auf as usize) % PLL_LIST.len()];
+            let m = case_of(seq, auf) * case_of(pll, 0);
+            let (j, a) = recognize_oll(&m).unwrap();
+            assert_eq!(j, i);
+            let cs = with_auf(OLL_LIST[j], a, 0);
+            assert!(oll_solved(&(matrix::of_seq(&cs) * m)));
+        }
+    }
+}
+
+/// Which side center each side sticker of the U layer matches.
+fn pll_signature(mat: &PermutationMatrix) -> u32 {
+    let centers = [R[4], L[4], F[4], B[4]];
+    let mut sig = 0;
+    let sides = ll_facelets().into_iter().filter(|&f| !U.contains(&f));
+    for (i, f) in sides.enumerate() {
+        let k = centers
+            .iter()
+            .position(|&c| same_color_check(mat, [c, f]))
+            .unwrap();
+        sig |= (k as u32) << (2 * i);
+    }
+    sig
+}
+static PLL_CASES: Lazy<HashMap<u32, (usize, i8, i8)>> = Lazy::new(|| {
+    let mut tbl = HashMap::new();
+    for (i, (_, seq)) in PLL_LIST.iter().enumerate() {
+        for pre in 0..4 {
+            for post in 0..4 {
+                let m = matrix::of_seq(&with_auf(seq, pre, post)).inv();
+                tbl.entry(pll_signature(&m)).or_insert((i, pre, post));
+            }
+        }
+    }
+    tbl
+});
+
+/// Recognize the PLL case.
+///
+/// Returns the name in `PLL_LIST` and the number of U turns to do before and after the algorithm.
+/// `None` if the OLL isn't solved or only the AUF is left.
+pub fn recognize_pll(mat: &PermutationMatrix) -> Option<(&'static str, i8, i8)> {
+    if !oll_solved(mat) {
+        return None;
+    }
+    let (i, pre, post) = PLL_CASES.get(&pll_signature(mat))?;
+    Some((PLL_LIST[*i].0, *pre, *post))
+}
+#[test]
+fn test_recognize_pll() {
+    // Every permutation of the last layer but the U turns.
+    assert_eq!(PLL_CASES.len(), 4 * 72 - 4);
+    for k in 0..4 {
+        let m = matrix::of_seq(&[Command(Move::U, k)]);
+        assert_eq!(recognize_pll(&m), None);
+    }
+    for (name, seq) in PLL_LIST {
+        for pre in 0..4 {
+            for post in 0..4 {
+                let m = matrix::of_seq(&with_auf(seq, pre, post)).inv();
+                let (found, a, b) = recognize_pll(&m).unwrap();
+                assert_eq!(found, name);
+                let (_, seq) = PLL_LIST.iter().find(|(n, _)| *n == found).unwrap();
+                assert!(solved(&(matrix::of_seq(&with_auf(seq, a, b)) * m)));
+            }
+        }
+    }
+}
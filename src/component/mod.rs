@@ -12,7 +12,9 @@ use crate::coord::*;
 use crate::matrix::*;
 use crate::*;
 use nalgebra_glm::{vec3, vec4, Vec3, Vec4};
+use std::cell::RefCell;
 use std::collections::{HashSet, VecDeque};
+use std::rc::Rc;
 
 struct CubePiece {
     pub vertices: [Vec3; 8],
@@ -98,6 +100,7 @@ pub struct Cube {
     color_list: [Vec4; 54],
 
     command_queue: VecDeque<Rotation>,
+    command_stream: Option<CommandStream>,
     cur_rotation: Option<RotationProgress>,
     next_state: PermutationMatrix,
 
@@ -110,11 +113,28 @@ pub enum Msg {
     Render(f64),
 }
 
+/// Lazy sequence of rotations.
+///
+/// Rotations are pulled one by one so huge sequences never have to be allocated.
+#[derive(Clone)]
+pub struct CommandStream(Rc<RefCell<dyn Iterator<Item = Rotation>>>);
+impl CommandStream {
+    pub fn new(iter: impl Iterator<Item = Rotation> + 'static) -> Self {
+        Self(Rc::new(RefCell::new(iter)))
+    }
+    fn next(&self) -> Option<Rotation> {
+        self.0.borrow_mut().next()
+    }
+}
+
 #[derive(yew::Properties, Clone)]
 pub struct Props {
     pub init_state: PermutationMatrix,
     #[prop_or_default]
     pub command_list: Vec<Rotation>,
+    /// Rotations played after `command_list`.
+    #[prop_or_default]
+    pub command_stream: Option<CommandStream>,
     #[prop_or_default]
     pub blacklist: HashSet<u8>,
 }
@@ -175,6 +195,7 @@ impl Component for Cube {
             state: props.init_state,
             next_state: props.init_state,
             command_queue,
+            command_stream: props.command_stream,
             blacklist: props.blacklist,
         }
     }
@@ -275,6 +296,7 @@ impl Component for Cube {
         self.next_state = props.init_state;
         self.blacklist = props.blacklist;
         self.command_queue = VecDeque::from_iter(props.command_list);
+        self.command_stream = props.command_stream;
         self.cur_rotation = None;
 
         let render_frame = self.link.callback(Msg::Render);
@@ -305,7 +327,11 @@ impl Cube {
         if should_dequeue {
             self.cur_rotation = None;
             self.state = self.next_state;
-            if let Some(head) = self.command_queue.pop_front() {
+            let head = match self.command_queue.pop_front() {
+                Some(x) => Some(x),
+                None => self.command_stream.as_ref().and_then(|s| s.next()),
+            };
+            if let Some(head) = head {
                 let rot = head;
                 let mut pieces = HashSet::new();
                 for i in 0..3 {
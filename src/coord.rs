@@ -165,6 +165,24 @@ fn test_surface_index_of_no_dup() {
         }
     }
 }
+#[test]
+fn test_wide_moves() {
+    use crate::matrix::of_seq;
+    let of = |cs: &[(Move, i8)]| {
+        let cs: Vec<Command> = cs.iter().map(|&(m, k)| Command(m, k)).collect();
+        of_seq(&cs)
+    };
+    assert_eq!(of(&[(Move::r, 1)]), of(&[(Move::R, 1), (Move::M, -1)]));
+    assert_eq!(of(&[(Move::l, 1)]), of(&[(Move::L, 1), (Move::M, 1)]));
+    assert_eq!(of(&[(Move::u, 1)]), of(&[(Move::U, 1), (Move::E, -1)]));
+    assert_eq!(of(&[(Move::d, 1)]), of(&[(Move::D, 1), (Move::E, 1)]));
+    assert_eq!(of(&[(Move::f, 1)]), of(&[(Move::F, 1), (Move::S, 1)]));
+    assert_eq!(of(&[(Move::b, 1)]), of(&[(Move::B, 1), (Move::S, -1)]));
+    // b turns like B, not like the mirrored f'.
+    assert_ne!(of(&[(Move::b, 1)]), of(&[(Move::B, -1), (Move::S, 1)]));
+    assert_ne!(of(&[(Move::b, 1)]), of(&[(Move::f, -1)]));
+    assert_eq!(of(&[(Move::F, 1), (Move::b, -1)]), of(&[(Move::z, 1)]));
+}
 
 /// Rotation Axis
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
@@ -278,7 +296,7 @@ impl Cache {
             (Move::u, 1) => rot(Axis::Y, 0b110, 1),
             (Move::d, 1) => rot(Axis::Y, 0b011, -1),
             (Move::f, 1) => rot(Axis::Z, 0b110, 1),
-            (Move::b, 1) => rot(Axis::Z, 0b011, 1),
+            (Move::b, 1) => rot(Axis::Z, 0b011, -1),
             (Move::x, 1) => rot(Axis::X, 0b111, 1),
             (Move::y, 1) => rot(Axis::Y, 0b111, 1),
             (Move::z, 1) => rot(Axis::Z, 0b111, 1),
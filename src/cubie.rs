@@ -0,0 +1,645 @@
+//! Piece level representation of the cube.
+//!
+//! `PermutationMatrix` is easy to compose but solvers need to know
+//! where each corner and edge is and how it is twisted.
+//! `CubieCube` keeps the permutation and the orientation of the pieces
+//! and gives them coordinates that are used to index tables.
+//!
+//! The orders of the pieces follow Kociemba's convention.
+//! - Corners: URF, UFL, ULB, UBR, DFR, DLF, DBL, DRB
+//! - Edges: UR, UF, UL, UB, DR, DF, DL, DB, FR, FL, BL, BR
+//!
+//! The orientation of a corner is the position of its U/D sticker.
+//! The orientation of an edge is 0 if its U/D sticker (F/B sticker for E-slice edges)
+//! is on the U/D face (F/B face for E-slice positions).
+
+use crate::coord::{self, Piece, Surface, SurfaceIndex};
+use crate::matrix::{self, PermutationMatrix};
+use crate::{Command, Move};
+use once_cell::sync::Lazy;
+
+/// Faces in the order of the move index.
+pub const FACES: [Move; 6] = [Move::U, Move::R, Move::F, Move::D, Move::L, Move::B];
+/// Repeatance in the order of the move index.
+pub const POWERS: [i8; 3] = [1, 2, -1];
+/// The number of face turns.
+pub const N_MOVE: usize = 18;
+
+/// Face turn of the move index. The index is 3 * face + power.
+pub fn command_of(m: usize) -> Command {
+    Command(FACES[m / 3], POWERS[m % 3])
+}
+/// Inverse function of `command_of`. `None` if the command is not a face turn.
+pub fn index_of(c: Command) -> Option<usize> {
+    let f = FACES.iter().position(|&mov| mov == c.0)?;
+    let p = match c.1.rem_euclid(4) {
+        1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => return None,
+    };
+    Some(3 * f + p)
+}
+
+const CORNER_PIECES: [(Piece, [Surface; 3]); 8] = {
+    use Surface::*;
+    [
+        (Piece(2, 2, 2), [U, R, F]),
+        (Piece(0, 2, 2), [U, F, L]),
+        (Piece(0, 2, 0), [U, L, B]),
+        (Piece(2, 2, 0), [U, B, R]),
+        (Piece(2, 0, 2), [D, F, R]),
+        (Piece(0, 0, 2), [D, L, F]),
+        (Piece(0, 0, 0), [D, B, L]),
+        (Piece(2, 0, 0), [D, R, B]),
+    ]
+};
+const EDGE_PIECES: [(Piece, [Surface; 2]); 12] = {
+    use Surface::*;
+    [
+        (Piece(2, 2, 1), [U, R]),
+        (Piece(1, 2, 2), [U, F]),
+        (Piece(0, 2, 1), [U, L]),
+        (Piece(1, 2, 0), [U, B]),
+        (Piece(2, 0, 1), [D, R]),
+        (Piece(1, 0, 2), [D, F]),
+        (Piece(0, 0, 1), [D, L]),
+        (Piece(1, 0, 0), [D, B]),
+        (Piece(2, 1, 2), [F, R]),
+        (Piece(0, 1, 2), [F, L]),
+        (Piece(0, 1, 0), [B, L]),
+        (Piece(2, 1, 0), [B, R]),
+    ]
+};
+
+fn facelet(piece: Piece, surface: Surface) -> u8 {
+    let SurfaceIndex(s, i, j) = coord::surface_index_of(piece, surface).unwrap();
+    coord::surface_number(s, i, j)
+}
+struct Facelets {
+    corners: [[u8; 3]; 8],
+    edges: [[u8; 2]; 12],
+}
+static FACELETS: Lazy<Facelets> = Lazy::new(|| {
+    let mut corners = [[0; 3]; 8];
+    for i in 0..8 {
+        let (p, s) = CORNER_PIECES[i];
+        for k in 0..3 {
+            corners[i][k] = facelet(p, s[k]);
+        }
+    }
+    let mut edges = [[0; 2]; 12];
+    for i in 0..12 {
+        let (p, s) = EDGE_PIECES[i];
+        for k in 0..2 {
+            edges[i][k] = facelet(p, s[k]);
+        }
+    }
+    Facelets { corners, edges }
+});
+
+/// Sticker positions of the corner at the position. The U/D sticker comes first and then clockwise.
+pub fn corner_facelets(i: usize) -> [u8; 3] {
+    FACELETS.corners[i]
+}
+/// Sticker positions of the edge at the position. The reference sticker comes first.
+pub fn edge_facelets(i: usize) -> [u8; 2] {
+    FACELETS.edges[i]
+}
+/// Sticker positions of the centers in the order of `coord::SURFACE_LIST`.
+pub fn center_facelets() -> [u8; 6] {
+    let mut v = [0; 6];
+    for (n, x) in v.iter_mut().enumerate() {
+        *x = 9 * n as u8 + 4;
+    }
+    v
+}
+
+/// Binomial coefficient.
+pub(crate) fn choose(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    let mut x = 1;
+    for i in 0..k {
+        x = x * (n - i) / (i + 1);
+    }
+    x
+}
+fn rotate_left(a: &mut [u8], l: usize, r: usize) {
+    a[l..=r].rotate_left(1);
+}
+fn rotate_right(a: &mut [u8], l: usize, r: usize) {
+    a[l..=r].rotate_right(1);
+}
+/// Index of a permutation in [0, n!).
+pub(crate) fn perm_index(p: &[u8]) -> usize {
+    let n = p.len();
+    let mut buf = [0u8; 12];
+    let perm = &mut buf[..n];
+    perm.copy_from_slice(p);
+    let mut b = 0;
+    for j in (1..n).rev() {
+        let mut k = 0;
+        while perm[j] as usize != j {
+            rotate_left(perm, 0, j);
+            k += 1;
+        }
+        b = (j + 1) * b + k;
+    }
+    b
+}
+/// Inverse function of `perm_index`.
+pub(crate) fn perm_of(mut idx: usize, perm: &mut [u8]) {
+    for (j, x) in perm.iter_mut().enumerate() {
+        *x = j as u8;
+    }
+    for j in 0..perm.len() {
+        let mut k = idx % (j + 1);
+        idx /= j + 1;
+        while k > 0 {
+            rotate_right(perm, 0, j);
+            k -= 1;
+        }
+    }
+}
+
+/// Cube state at the piece level.
+///
+/// `cp[i]` is the corner at the position `i` and `co[i]` is its orientation.
+/// The centers are fixed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CubieCube {
+    pub cp: [u8; 8],
+    pub co: [u8; 8],
+    pub ep: [u8; 12],
+    pub eo: [u8; 12],
+}
+impl Default for CubieCube {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+impl CubieCube {
+    pub fn identity() -> Self {
+        let mut cp = [0; 8];
+        let mut ep = [0; 12];
+        for (i, x) in cp.iter_mut().enumerate() {
+            *x = i as u8;
+        }
+        for (i, x) in ep.iter_mut().enumerate() {
+            *x = i as u8;
+        }
+        Self {
+            cp,
+            co: [0; 8],
+            ep,
+            eo: [0; 12],
+        }
+    }
+    /// Read the pieces from a state.
+    ///
+    /// `None` if the centers are not at the home positions or the state is broken.
+    pub fn from_matrix(m: &PermutationMatrix) -> Option<Self> {
+        let inv = &m.inv_perm;
+        for c in center_facelets() {
+            if inv[c as usize] != c {
+                return None;
+            }
+        }
+        let mut x = Self::identity();
+        let mut used = 0u32;
+        for i in 0..8 {
+            let f = corner_facelets(i);
+            let mut found = false;
+            'search: for ori in 0..3 {
+                let o = inv[f[ori] as usize];
+                for j in 0..8 {
+                    let g = corner_facelets(j);
+                    if g[0] == o {
+                        if inv[f[(ori + 1) % 3] as usize] != g[1]
+                            || inv[f[(ori + 2) % 3] as usize] != g[2]
+                        {
+                            return None;
+                        }
+                        x.cp[i] = j as u8;
+                        x.co[i] = ori as u8;
+                        used |= 1 << j;
+                        found = true;
+                        break 'search;
+                    }
+                }
+            }
+            if !found {
+                return None;
+            }
+        }
+        for i in 0..12 {
+            let f = edge_facelets(i);
+            let o0 = inv[f[0] as usize];
+            let o1 = inv[f[1] as usize];
+            let mut found = false;
+            for j in 0..12 {
+                let g = edge_facelets(j);
+                if g == [o0, o1] || g == [o1, o0] {
+                    x.ep[i] = j as u8;
+                    x.eo[i] = if g[0] == o0 { 0 } else { 1 };
+                    used |= 1 << (8 + j);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return None;
+            }
+        }
+        if used != (1 << 20) - 1 {
+            return None;
+        }
+        Some(x)
+    }
+    /// Inverse function of `from_matrix`.
+    pub fn to_matrix(&self) -> PermutationMatrix {
+        let mut inv = [0u8; 54];
+        for (k, x) in inv.iter_mut().enumerate() {
+            *x = k as u8;
+        }
+        for i in 0..8 {
+            let f = corner_facelets(i);
+            let g = corner_facelets(self.cp[i] as usize);
+            for t in 0..3 {
+                inv[f[(self.co[i] as usize + t) % 3] as usize] = g[t];
+            }
+        }
+        for i in 0..12 {
+            let f = edge_facelets(i);
+            let g = edge_facelets(self.ep[i] as usize);
+            for t in 0..2 {
+                inv[f[(self.eo[i] as usize + t) % 2] as usize] = g[t];
+            }
+        }
+        PermutationMatrix { inv_perm: inv }
+    }
+    /// Apply `other` after `self`.
+    pub fn multiply(&self, other: &Self) -> Self {
+        let mut x = Self::identity();
+        for i in 0..8 {
+            let j = other.cp[i] as usize;
+            x.cp[i] = self.cp[j];
+            x.co[i] = (self.co[j] + other.co[i]) % 3;
+        }
+        for i in 0..12 {
+            let j = other.ep[i] as usize;
+            x.ep[i] = self.ep[j];
+            x.eo[i] = (self.eo[j] + other.eo[i]) % 2;
+        }
+        x
+    }
+    pub fn inv(&self) -> Self {
+        let mut x = Self::identity();
+        for i in 0..8 {
+            let j = self.cp[i] as usize;
+            x.cp[j] = i as u8;
+            x.co[j] = (3 - self.co[i]) % 3;
+        }
+        for i in 0..12 {
+            let j = self.ep[i] as usize;
+            x.ep[j] = i as u8;
+            x.eo[j] = self.eo[i];
+        }
+        x
+    }
+    /// Apply the face turn of the move index.
+    pub fn apply(&self, m: usize) -> Self {
+        self.multiply(&MOVE_CUBES[m])
+    }
+    pub fn is_solved(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    /// Orientation of the corners in [0, 3^7).
+    pub fn twist(&self) -> usize {
+        self.co[..7].iter().fold(0, |t, &x| 3 * t + x as usize)
+    }
+    pub fn set_twist(&mut self, mut t: usize) {
+        let mut sum = 0;
+        for i in (0..7).rev() {
+            self.co[i] = (t % 3) as u8;
+            sum += self.co[i];
+            t /= 3;
+        }
+        self.co[7] = (3 - sum % 3) % 3;
+    }
+    /// Orientation of the edges in [0, 2^11).
+    pub fn flip(&self) -> usize {
+        self.eo[..11].iter().fold(0, |t, &x| 2 * t + x as usize)
+    }
+    pub fn set_flip(&mut self, mut f: usize) {
+        let mut sum = 0;
+        for i in (0..11).rev() {
+            self.eo[i] = (f % 2) as u8;
+            sum += self.eo[i];
+            f /= 2;
+        }
+        self.eo[11] = sum % 2;
+    }
+    /// Positions and the permutation of the E-slice edges in [0, 11880).
+    ///
+    /// It is less than 24 if the E-slice edges are in the E-slice.
+    pub fn slice_sorted(&self) -> usize {
+        let mut a = 0;
+        let mut x = 0;
+        let mut edge4 = [0u8; 4];
+        for j in (0..12).rev() {
+            if self.ep[j] >= 8 {
+                a += choose(11 - j, x + 1);
+                edge4[3 - x] = self.ep[j] - 8;
+                x += 1;
+            }
+        }
+        24 * a + perm_index(&edge4)
+    }
+    pub fn set_slice_sorted(&mut self, idx: usize) {
+        let mut slice_edge = [0u8; 4];
+        perm_of(idx % 24, &mut slice_edge);
+        let mut a = idx / 24;
+        let mut x = 4;
+        let mut k = 0;
+        for (j, e) in self.ep.iter_mut().enumerate() {
+            if x > 0 && a >= choose(11 - j, x) {
+                *e = slice_edge[4 - x] + 8;
+                a -= choose(11 - j, x);
+                x -= 1;
+            } else {
+                *e = k;
+                k += 1;
+            }
+        }
+    }
+    /// Positions of the E-slice edges in [0, 495).
+    pub fn slice(&self) -> usize {
+        self.slice_sorted() / 24
+    }
+    /// Permutation of the corners in [0, 8!).
+    pub fn corners(&self) -> usize {
+        perm_index(&self.cp)
+    }
+    pub fn set_corners(&mut self, idx: usize) {
+        perm_of(idx, &mut self.cp);
+    }
+    /// Permutation of the U/D edges in [0, 8!).
+    ///
+    /// Only valid if the E-slice edges are in the E-slice.
+    pub fn ud_edges(&self) -> usize {
+        perm_index(&self.ep[..8])
+    }
+    pub fn set_ud_edges(&mut self, idx: usize) {
+        perm_of(idx, &mut self.ep[..8]);
+        for j in 8..12 {
+            self.ep[j] = j as u8;
+        }
+    }
+    /// Permutation of the edges in [0, 12!).
+    pub fn edges(&self) -> usize {
+        perm_index(&self.ep)
+    }
+    pub fn set_edges(&mut self, idx: usize) {
+        perm_of(idx, &mut self.ep);
+    }
+    /// Check if the permutation and orientation can be reached by moves.
+    pub fn is_solvable(&self) -> bool {
+        let co: u8 = self.co.iter().sum();
+        let eo: u8 = self.eo.iter().sum();
+        co.is_multiple_of(3) && eo.is_multiple_of(2) && parity(&self.cp) == parity(&self.ep)
+    }
+}
+impl std::ops::Mul for CubieCube {
+    type Output = Self;
+    /// Apply `rhs` after `self`.
+    fn mul(self, rhs: Self) -> Self {
+        self.multiply(&rhs)
+    }
+}
+/// Parity of a permutation. 0 for even and 1 for odd.
+pub fn parity(perm: &[u8]) -> u8 {
+    let mut p = 0;
+    for i in 0..perm.len() {
+        for j in i + 1..perm.len() {
+            if perm[i] > perm[j] {
+                p ^= 1;
+            }
+        }
+    }
+    p
+}
+
+static MOVE_CUBES: Lazy<[CubieCube; N_MOVE]> = Lazy::new(|| {
+    let mut v = [CubieCube::identity(); N_MOVE];
+    for (m, x) in v.iter_mut().enumerate() {
+        let mat = matrix::of(coord::rotation_of(command_of(m)));
+        *x = CubieCube::from_matrix(&mat).unwrap();
+    }
+    v
+});
+
+/// The 24 whole cube rotations as sequences of x, y and z.
+pub fn rotations() -> &'static [Vec<Command>] {
+    &ROTATIONS
+}
+static ROTATIONS: Lazy<Vec<Vec<Command>>> = Lazy::new(|| {
+    let mut v: Vec<(PermutationMatrix, Vec<Command>)> =
+        vec![(PermutationMatrix::identity(), vec![])];
+    let mut k = 0;
+    while k < v.len() {
+        for mov in [Move::x, Move::y, Move::z] {
+            let c = Command(mov, 1);
+            let m = matrix::of(coord::rotation_of(c)) * v[k].0;
+            if v.iter().all(|x| x.0 != m) {
+                let mut seq = v[k].1.clone();
+                seq.push(c);
+                v.push((m, seq));
+            }
+        }
+        k += 1;
+    }
+    v.into_iter().map(|x| x.1).collect()
+});
+
+/// A state with the centers moved back to the home positions.
+///
+/// A solution for the `cube` can be turned into a solution for the original state by `relabel`.
+#[derive(Clone, Debug)]
+pub struct Oriented {
+    pub cube: CubieCube,
+    /// Index of `rotations` that moves the centers back.
+    pub rotation: usize,
+}
+impl Oriented {
+    /// Read the pieces of any reachable state.
+    pub fn new(m: &PermutationMatrix) -> Option<Self> {
+        for (k, rot) in rotations().iter().enumerate() {
+            let r = matrix::of_seq(rot) * *m;
+            if let Some(cube) = CubieCube::from_matrix(&r) {
+                return Some(Self { cube, rotation: k });
+            }
+        }
+        None
+    }
+    /// Turn a sequence of face turns for `cube` into a sequence for the original state.
+    ///
+    /// The result is the same up to the whole cube rotation.
+    pub fn relabel(&self, cs: &[Command]) -> Vec<Command> {
+        cs.iter().map(|&c| relabel(c, self.rotation)).collect()
+    }
+}
+static RELABEL: Lazy<Vec<[usize; N_MOVE]>> = Lazy::new(|| {
+    let mut v = vec![];
+    for rot in rotations() {
+        let r = matrix::of_seq(rot);
+        let mut tbl = [0; N_MOVE];
+        for (m, x) in tbl.iter_mut().enumerate() {
+            let target = r.inv() * matrix::of_seq(&[command_of(m)]) * r;
+            *x = (0..N_MOVE)
+                .find(|&k| matrix::of_seq(&[command_of(k)]) == target)
+                .unwrap();
+        }
+        v.push(tbl);
+    }
+    v
+});
+/// Turn a face turn seen from the rotated frame into the face turn in the original frame.
+pub fn relabel(c: Command, rotation: usize) -> Command {
+    match index_of(c) {
+        Some(m) => command_of(RELABEL[rotation][m]),
+        None => c,
+    }
+}
+
+/// The state seen from the frame rotated by `rotations()[rotation]`.
+///
+/// Applying `c` to the result is the same as applying `relabel(c, rotation)` to `x`.
+pub fn conjugate(x: &CubieCube, rotation: usize) -> CubieCube {
+    let r = matrix::of_seq(&rotations()[rotation]);
+    CubieCube::from_matrix(&(r * x.to_matrix() * r.inv())).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::solved;
+
+    #[test]
+    fn test_facelets_clockwise() {
+        fn normal(s: Surface) -> [i32; 3] {
+            match s {
+                Surface::R => [1, 0, 0],
+                Surface::L => [-1, 0, 0],
+                Surface::U => [0, 1, 0],
+                Surface::D => [0, -1, 0],
+                Surface::F => [0, 0, 1],
+                Surface::B => [0, 0, -1],
+            }
+        }
+        for (_, s) in CORNER_PIECES {
+            let a = normal(s[0]);
+            let b = normal(s[1]);
+            let c = normal(s[2]);
+            let cross = [
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ];
+            assert_eq!(cross[0] * c[0] + cross[1] * c[1] + cross[2] * c[2], -1);
+        }
+    }
+    #[test]
+    fn test_from_matrix() {
+        assert_eq!(
+            CubieCube::from_matrix(&PermutationMatrix::identity()),
+            Some(CubieCube::identity())
+        );
+        for _ in 0..100 {
+            let cs: Vec<Command> = (0..30)
+                .map(|_| command_of(rand::random::<usize>() % N_MOVE))
+                .collect();
+            let m = matrix::of_seq(&cs);
+            let x = CubieCube::from_matrix(&m).unwrap();
+            assert!(x.is_solvable());
+            assert_eq!(x.to_matrix(), m);
+            let mut y = CubieCube::identity();
+            for &c in &cs {
+                y = y.apply(index_of(c).unwrap());
+            }
+            assert_eq!(x, y);
+            assert_eq!(x * x.inv(), CubieCube::identity());
+        }
+        let m = matrix::of_seq(&[Command(Move::M, 1)]);
+        assert_eq!(CubieCube::from_matrix(&m), None);
+    }
+    #[test]
+    fn test_coordinates() {
+        for _ in 0..100 {
+            let cs = crate::random(30);
+            let x = Oriented::new(&matrix::of_seq(&cs)).unwrap().cube;
+            let mut y = CubieCube::identity();
+            y.set_twist(x.twist());
+            assert_eq!(y.co, x.co);
+            y.set_flip(x.flip());
+            assert_eq!(y.eo, x.eo);
+            y.set_corners(x.corners());
+            assert_eq!(y.cp, x.cp);
+            y.set_edges(x.edges());
+            assert_eq!(y.ep, x.ep);
+            let mut z = CubieCube::identity();
+            z.set_slice_sorted(x.slice_sorted());
+            assert_eq!(z.slice_sorted(), x.slice_sorted());
+            for j in 0..12 {
+                assert_eq!(z.ep[j] >= 8, x.ep[j] >= 8);
+                if x.ep[j] >= 8 {
+                    assert_eq!(z.ep[j], x.ep[j]);
+                }
+            }
+        }
+        let x = CubieCube::identity();
+        assert_eq!(x.slice_sorted(), 0);
+        assert_eq!(x.corners(), 0);
+        assert_eq!(x.ud_edges(), 0);
+        assert_eq!(choose(12, 4), 495);
+    }
+    #[test]
+    fn test_rotations() {
+        assert_eq!(rotations().len(), 24);
+        for _ in 0..100 {
+            let cs = crate::random(20);
+            let o = Oriented::new(&matrix::of_seq(&cs)).unwrap();
+            let ts: Vec<Command> = (0..10)
+                .map(|_| command_of(rand::random::<usize>() % N_MOVE))
+                .collect();
+            let mut expected = o.cube;
+            for &t in &ts {
+                expected = expected.apply(index_of(t).unwrap());
+            }
+            let mut seq = cs.clone();
+            seq.extend(o.relabel(&ts));
+            let m = matrix::of_seq(&seq);
+            assert_eq!(Oriented::new(&m).unwrap().cube, expected);
+            assert_eq!(solved(&m), expected.is_solved());
+        }
+    }
+    #[test]
+    fn test_conjugate() {
+        for k in 0..24 {
+            let cs: Vec<Command> = (0..10)
+                .map(|_| command_of(rand::random::<usize>() % N_MOVE))
+                .collect();
+            let mut x = CubieCube::identity();
+            for &c in &cs {
+                x = x.apply(index_of(c).unwrap());
+            }
+            for m in 0..N_MOVE {
+                let y = x.apply(index_of(relabel(command_of(m), k)).unwrap());
+                assert_eq!(conjugate(&x, k).apply(m), conjugate(&y, k));
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Print move sequences in various notation styles.
+//!
+//! The default style prints sequences like `R U R' U'`
+//! and the output can be read back by `parser::parse`.
+
+use crate::{flatten, Command, Elem, Move};
+use std::fmt;
+
+/// How to print counter-clockwise rotations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimeStyle {
+    /// R' and R2'
+    Apostrophe,
+    /// R' and R2. The direction of half turns is dropped.
+    Undirected,
+    /// R’ and R2’ with typographic apostrophe.
+    Typographic,
+}
+
+/// How to print groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupStyle {
+    /// (RUR')2
+    Parentheses,
+    /// RUR'RUR'
+    Expand,
+}
+
+/// How to print wide moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WideStyle {
+    /// r
+    Lowercase,
+    /// Rw
+    Suffix,
+}
+
+/// Options to format a move sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// Put a space between elements.
+    pub spacing: bool,
+    pub prime: PrimeStyle,
+    pub group: GroupStyle,
+    pub wide: WideStyle,
+    /// Break lines so no line is longer than this. A single element longer than this is not split.
+    pub line_width: Option<usize>,
+}
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            spacing: true,
+            prime: PrimeStyle::Apostrophe,
+            group: GroupStyle::Parentheses,
+            wide: WideStyle::Lowercase,
+            line_width: None,
+        }
+    }
+}
+impl Style {
+    /// The style used in this crate's lists like `cfop::PLL_LIST`.
+    pub fn compact() -> Self {
+        Self {
+            spacing: false,
+            ..Self::default()
+        }
+    }
+}
+
+fn move_str(mov: Move, wide: WideStyle) -> &'static str {
+    use Move::*;
+    match (mov, wide) {
+        (r, WideStyle::Suffix) => "Rw",
+        (l, WideStyle::Suffix) => "Lw",
+        (u, WideStyle::Suffix) => "Uw",
+        (d, WideStyle::Suffix) => "Dw",
+        (f, WideStyle::Suffix) => "Fw",
+        (b, WideStyle::Suffix) => "Bw",
+        (R, _) => "R",
+        (L, _) => "L",
+        (U, _) => "U",
+        (D, _) => "D",
+        (F, _) => "F",
+        (B, _) => "B",
+        (r, _) => "r",
+        (l, _) => "l",
+        (u, _) => "u",
+        (d, _) => "d",
+        (f, _) => "f",
+        (b, _) => "b",
+        (M, _) => "M",
+        (E, _) => "E",
+        (S, _) => "S",
+        (x, _) => "x",
+        (y, _) => "y",
+        (z, _) => "z",
+    }
+}
+fn rep_str(rep: i8, prime: PrimeStyle) -> String {
+    let mut s = String::new();
+    let n = rep.abs();
+    if n != 1 {
+        s.push_str(&n.to_string());
+    }
+    if rep < 0 {
+        match prime {
+            PrimeStyle::Apostrophe => s.push('\''),
+            PrimeStyle::Typographic => s.push('’'),
+            PrimeStyle::Undirected => {
+                if n == 1 {
+                    s.push('\'')
+                }
+            }
+        }
+    }
+    s
+}
+fn command_str(c: Command, style: &Style) -> String {
+    let mut s = move_str(c.0, style.wide).to_owned();
+    s.push_str(&rep_str(c.1, style.prime));
+    s
+}
+fn commands_str(cs: &[Command], style: &Style) -> Vec<String> {
+    cs.iter().map(|&c| command_str(c, style)).collect()
+}
+
+/// Format a command. e.g. R2'
+pub fn format_command(c: Command, style: &Style) -> String {
+    command_str(c, style)
+}
+
+/// Format an element. e.g. (R U R')2
+pub fn format_elem(e: &Elem, style: &Style) -> String {
+    let sep = if style.spacing { " " } else { "" };
+    match e {
+        Elem::One(c) => command_str(*c, style),
+        Elem::Group(cs, rep) => match style.group {
+            GroupStyle::Parentheses => {
+                format!(
+                    "({}){}",
+                    commands_str(cs, style).join(sep),
+                    rep_str(*rep, style.prime)
+                )
+            }
+            GroupStyle::Expand => {
+                let cs = flatten(vec![Elem::Group(cs.clone(), *rep)]);
+                commands_str(&cs, style).join(sep)
+            }
+        },
+    }
+}
+
+fn layout(tokens: Vec<String>, style: &Style) -> String {
+    let sep = if style.spacing { " " } else { "" };
+    let width = match style.line_width {
+        Some(w) => w,
+        None => return tokens.join(sep),
+    };
+    let mut lines = vec![];
+    let mut line = String::new();
+    for tok in tokens {
+        if line.is_empty() {
+            line = tok;
+            continue;
+        }
+        let len = line.chars().count() + sep.len() + tok.chars().count();
+        if len > width {
+            lines.push(std::mem::replace(&mut line, tok));
+        } else {
+            line.push_str(sep);
+            line.push_str(&tok);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Format a move sequence.
+pub fn format(elems: &[Elem], style: &Style) -> String {
+    let tokens = elems.iter().map(|e| format_elem(e, style)).collect();
+    layout(tokens, style)
+}
+
+/// Format a move sequence without parentheses.
+pub fn format_commands(cs: &[Command], style: &Style) -> String {
+    layout(commands_str(cs, style), style)
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", command_str(*self, &Style::compact()))
+    }
+}
+impl fmt::Display for Elem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_elem(self, &Style::compact()))
+    }
+}
+
+#[test]
+fn test_format() {
+    use crate::parser::parse;
+    let elems = parse("(R2'U)(RUR')U'r").unwrap().1;
+    assert_eq!(format(&elems, &Style::default()), "(R2' U) (R U R') U' r");
+    assert_eq!(format(&elems, &Style::compact()), "(R2'U)(RUR')U'r");
+    let style = Style {
+        prime: PrimeStyle::Undirected,
+        group: GroupStyle::Expand,
+        wide: WideStyle::Suffix,
+        ..Style::default()
+    };
+    assert_eq!(format(&elems, &style), "R2 U R U R' U' Rw");
+    let style = Style {
+        prime: PrimeStyle::Typographic,
+        ..Style::compact()
+    };
+    assert_eq!(format(&elems, &style), "(R2’U)(RUR’)U’r");
+    assert_eq!(Command(Move::u, -2).to_string(), "u2'");
+    assert_eq!(
+        Elem::Group(vec![Command(Move::M, 1)], 2).to_string(),
+        "(M)2"
+    );
+}
+#[test]
+fn test_format_line_width() {
+    let cs = vec![Command(Move::R, 1); 5];
+    let style = Style {
+        line_width: Some(4),
+        ..Style::default()
+    };
+    assert_eq!(format_commands(&cs, &style), "R R\nR R\nR");
+    let style = Style {
+        line_width: Some(1),
+        ..Style::default()
+    };
+    assert_eq!(format_commands(&[Command(Move::R, -2)], &style), "R2'");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::MOVE_LIST;
+    use proptest::prelude::*;
+
+    fn arb_command() -> impl Strategy<Value = Command> {
+        (0..18usize, prop::sample::select(vec![-2i8, -1, 1, 2]))
+            .prop_map(|(i, rep)| Command(MOVE_LIST[i], rep))
+    }
+    fn arb_elem() -> impl Strategy<Value = Elem> {
+        prop_oneof![
+            arb_command().prop_map(Elem::One),
+            (
+                prop::collection::vec(arb_command(), 1..6),
+                prop::sample::select(vec![-2i8, -1, 1, 2])
+            )
+                .prop_map(|(cs, rep)| Elem::Group(cs, rep)),
+        ]
+    }
+    fn arb_style() -> impl Strategy<Value = Style> {
+        (any::<bool>(), any::<bool>(), prop::option::of(1..40usize)).prop_map(
+            |(spacing, suffix, line_width)| Style {
+                spacing,
+                prime: PrimeStyle::Apostrophe,
+                group: GroupStyle::Parentheses,
+                wide: if suffix {
+                    WideStyle::Suffix
+                } else {
+                    WideStyle::Lowercase
+                },
+                line_width,
+            },
+        )
+    }
+    proptest! {
+        #[test]
+        fn test_roundtrip(elems in prop::collection::vec(arb_elem(), 0..20), style in arb_style()) {
+            let s = format(&elems, &style);
+            assert_eq!(parse(&s).unwrap().1, elems);
+        }
+
+        #[test]
+        fn test_roundtrip_expand(elems in prop::collection::vec(arb_elem(), 0..20), style in arb_style()) {
+            let style = Style {
+                group: GroupStyle::Expand,
+                ..style
+            };
+            let s = format(&elems, &style);
+            let cs = flatten(parse(&s).unwrap().1);
+            assert_eq!(cs, flatten(elems));
+        }
+
+        #[test]
+        fn test_line_width(elems in prop::collection::vec(arb_elem(), 0..20), style in arb_style()) {
+            let s = format(&elems, &style);
+            if let Some(w) = style.line_width {
+                for line in s.lines() {
+                    // Only a single element can exceed the width.
+                    assert!(line.chars().count() <= w || parse(line).unwrap().1.len() == 1);
+                }
+            }
+        }
+    }
+}
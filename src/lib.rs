@@ -1,13 +1,21 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod block;
 pub mod cfop;
 #[cfg(feature = "component")]
 #[cfg_attr(docsrs, doc(cfg(feature = "component")))]
 pub mod component;
 pub mod coord;
+pub mod cubie;
+pub mod format;
 pub mod matrix;
+pub mod notation;
 
 pub mod parser;
+pub mod pattern;
+pub mod roux;
+pub mod solver;
+pub mod zz;
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum Move {
@@ -66,7 +74,7 @@ impl Command {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Elem {
     One(Command),
     Group(Vec<Command>, i8),
@@ -106,18 +114,174 @@ fn test_flatten() {
     assert_eq!(f, vec![Command(Move::R, -1), Command(Move::U, -1)]);
 }
 
+/// The axis of the move: 0 for R and L, 1 for U and D and 2 for F and B.
+pub(crate) fn axis(m: Move) -> usize {
+    match m {
+        Move::R | Move::L | Move::M | Move::r | Move::l | Move::x => 0,
+        Move::U | Move::D | Move::E | Move::u | Move::d | Move::y => 1,
+        _ => 2,
+    }
+}
+/// Cancel and merge the moves in a sequence.
+///
+/// The moves on the same axis commute so a run of them is merged by the move
+/// and sorted in the order of `MOVE_LIST`. `R L R'` becomes `L` and `R U U' R` becomes `R2`.
+pub fn simplify(cs: &[Command]) -> Vec<Command> {
+    let mut v: Vec<Command> = vec![];
+    for &c in cs {
+        let n = v
+            .iter()
+            .rev()
+            .take_while(|x| axis(x.0) == axis(c.0))
+            .count();
+        let start = v.len() - n;
+        match v[start..].iter().position(|x| x.0 == c.0) {
+            Some(i) => match (v[start + i].1 + c.1).rem_euclid(4) {
+                0 => {
+                    v.remove(start + i);
+                }
+                3 => v[start + i].1 = -1,
+                k => v[start + i].1 = k,
+            },
+            None => {
+                v.push(c);
+                v[start..].sort_by_key(|x| MOVE_LIST.iter().position(|&m| m == x.0));
+            }
+        }
+    }
+    v
+}
+#[test]
+fn test_simplify() {
+    let f = |s: &str| flatten(parser::parse(s).unwrap().1);
+    assert_eq!(simplify(&f("RLR'")), f("L"));
+    assert_eq!(simplify(&f("RUU'R")), f("R2"));
+    assert_eq!(simplify(&f("LR")), f("RL"));
+    assert_eq!(simplify(&f("R'R'FF'")), f("R2"));
+    assert_eq!(simplify(&f("UR2U'")), f("UR2U'"));
+    assert_eq!(simplify(&f("xMRx'")), f("RM"));
+    for _ in 0..100 {
+        let cs = random(30);
+        let x = simplify(&cs);
+        assert!(x.len() <= cs.len());
+        assert_eq!(matrix::of_seq(&x), matrix::of_seq(&cs));
+        assert_eq!(simplify(&x), x);
+    }
+}
+
+/// Iterator version of `flatten`.
+///
+/// Repeated groups are expanded lazily so the whole sequence is never allocated.
+pub fn flatten_iter<I: IntoIterator<Item = Elem>>(elems: I) -> FlattenIter<I::IntoIter> {
+    FlattenIter {
+        elems: elems.into_iter(),
+        group: None,
+        k: 0,
+    }
+}
+/// Iterator returned by `flatten_iter`.
+pub struct FlattenIter<I> {
+    elems: I,
+    group: Option<(Vec<Command>, i8)>,
+    k: usize,
+}
+impl<I: Iterator<Item = Elem>> Iterator for FlattenIter<I> {
+    type Item = Command;
+    fn next(&mut self) -> Option<Command> {
+        loop {
+            if let Some((cs, rep)) = &self.group {
+                let n = cs.len();
+                if self.k < n * rep.unsigned_abs() as usize {
+                    let j = self.k % n;
+                    self.k += 1;
+                    let c = if *rep > 0 {
+                        cs[j]
+                    } else {
+                        cs[n - 1 - j].prime()
+                    };
+                    return Some(c);
+                }
+                self.group = None;
+            }
+            match self.elems.next()? {
+                Elem::One(c) => return Some(c),
+                Elem::Group(cs, rep) => {
+                    self.group = Some((cs, rep));
+                    self.k = 0;
+                }
+            }
+        }
+    }
+}
+#[test]
+fn test_flatten_iter() {
+    let e = Elem::Group(vec![Command(Move::U, 1), Command(Move::R, 1)], -2);
+    let f: Vec<Command> = flatten_iter(vec![Elem::One(Command(Move::F, 1)), e]).collect();
+    assert_eq!(
+        f,
+        vec![
+            Command(Move::F, 1),
+            Command(Move::R, -1),
+            Command(Move::U, -1),
+            Command(Move::R, -1),
+            Command(Move::U, -1)
+        ]
+    );
+    for seq in cfop::PLL_LIST.iter().map(|x| x.1).chain(cfop::OLL_LIST) {
+        let f: Vec<Command> = flatten_iter(parser::parse(seq).unwrap().1).collect();
+        assert_eq!(f, flatten(parser::parse(seq).unwrap().1));
+    }
+}
+
+/// Only `next_u32` is used so the output for a seed doesn't depend on the platform.
+fn random_command<R: rand::RngCore>(rng: &mut R) -> Command {
+    let mov = MOVE_LIST[rng.next_u32() as usize % 18];
+    let rep = match rng.next_u32() % 4 {
+        0 => -2,
+        1 => -1,
+        2 => 1,
+        _ => 2,
+    };
+    Command(mov, rep)
+}
 /// Generate a scramble sequence.
 pub fn random(n: usize) -> Vec<Command> {
-    use rand::prelude::*;
+    random_iter(n).collect()
+}
+/// `random` with a fixed seed for reproducible scrambles.
+///
+/// The generator is ChaCha8 whose output is fixed for a seed across releases and platforms.
+pub fn random_seeded(n: usize, seed: u64) -> Vec<Command> {
+    use rand::SeedableRng;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
+    (0..n).map(|_| random_command(&mut rng)).collect()
+}
+/// Iterator version of `random`.
+pub fn random_iter(n: usize) -> impl Iterator<Item = Command> {
     let mut rng = rand::thread_rng();
-    let mut v = vec![];
-    for _ in 0..n {
-        let mov: usize = rng.gen();
-        let mov = MOVE_LIST[mov % 18];
-        let ran: i64 = rng.gen();
-        let rep = ran % 2 + 1;
-        let rep = if ran % 2 == 0 { rep } else { -rep };
-        v.push(Command(mov, rep as i8));
+    (0..n).map(move |_| random_command(&mut rng))
+}
+#[test]
+fn test_random() {
+    let mut reps = std::collections::HashSet::new();
+    for Command(_, rep) in random(1000) {
+        reps.insert(rep);
     }
-    v
+    let mut reps: Vec<i8> = reps.into_iter().collect();
+    reps.sort();
+    assert_eq!(reps, vec![-2, -1, 1, 2]);
+    assert_eq!(random_seeded(30, 1), random_seeded(30, 1));
+    assert_ne!(random_seeded(30, 1), random_seeded(30, 2));
+    // Fixed for the seed.
+    use Move::*;
+    assert_eq!(
+        random_seeded(5, 0),
+        vec![
+            Command(S, 2),
+            Command(L, 2),
+            Command(F, 1),
+            Command(r, -2),
+            Command(x, 2)
+        ]
+    );
 }
@@ -311,6 +311,18 @@ pub fn of(rot: coord::Rotation) -> PermutationMatrix {
     let mut cache = CACHE.lock().unwrap();
     cache.get(rot)
 }
+/// Get permutation from a sequence of `Command`s.
+pub fn of_seq(cs: &[Command]) -> PermutationMatrix {
+    of_iter(cs.iter().copied())
+}
+/// Get permutation from a lazy sequence of `Command`s.
+pub fn of_iter<I: IntoIterator<Item = Command>>(cs: I) -> PermutationMatrix {
+    let mut m = PermutationMatrix::identity();
+    for c in cs {
+        m = matof(c) * m;
+    }
+    m
+}
 #[cfg(test)]
 mod tests {
     use super::*;
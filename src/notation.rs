@@ -0,0 +1,181 @@
+//! Conversion between notation families.
+//!
+//! Different tools expect different spellings of the same algorithm.
+//! An algorithm is rewritten with the moves available in the target family
+//! and then printed in the family's spelling.
+
+use crate::format::{format_commands, PrimeStyle, Style, WideStyle};
+use crate::{Command, Move};
+
+/// Notation family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notation {
+    /// Face turns, slice moves like M and rotations. No wide moves.
+    Singmaster,
+    /// Face turns, wide moves like Rw and rotations. No slice moves.
+    Wca,
+    /// Face turns, wide moves like r, slice moves and rotations.
+    /// If `inner_slices` is true, slice moves are written like 2L instead of M.
+    Sign { inner_slices: bool },
+    /// Face turns, wide moves like Rw, slice moves and rotations.
+    CubingJs,
+}
+impl Notation {
+    /// Check if the move can be written in this notation.
+    pub fn supports(self, mov: Move) -> bool {
+        use Move::*;
+        match mov {
+            R | L | U | D | F | B | x | y | z => true,
+            r | l | u | d | f | b => self != Notation::Singmaster,
+            M | E | S => self != Notation::Wca,
+        }
+    }
+    fn style(self) -> Style {
+        match self {
+            Notation::Singmaster => Style {
+                prime: PrimeStyle::Undirected,
+                ..Style::default()
+            },
+            Notation::Wca => Style {
+                prime: PrimeStyle::Undirected,
+                wide: WideStyle::Suffix,
+                ..Style::default()
+            },
+            Notation::Sign { .. } => Style::default(),
+            Notation::CubingJs => Style {
+                wide: WideStyle::Suffix,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+/// Equivalent moves for a move.
+fn equivalent(c: Command) -> Vec<Command> {
+    use Move::*;
+    let Command(mov, k) = c;
+    match mov {
+        r => vec![Command(R, k), Command(M, -k)],
+        l => vec![Command(L, k), Command(M, k)],
+        u => vec![Command(U, k), Command(E, -k)],
+        d => vec![Command(D, k), Command(E, k)],
+        f => vec![Command(F, k), Command(S, k)],
+        b => vec![Command(B, k), Command(S, -k)],
+        M => vec![Command(x, -k), Command(R, k), Command(L, -k)],
+        E => vec![Command(y, -k), Command(U, k), Command(D, -k)],
+        S => vec![Command(z, k), Command(F, -k), Command(B, k)],
+        _ => vec![c],
+    }
+}
+
+/// Rewrite the algorithm with the moves available in the notation.
+pub fn convert(cs: &[Command], to: Notation) -> Vec<Command> {
+    let mut v = vec![];
+    let mut stack: Vec<Command> = cs.iter().rev().copied().collect();
+    while let Some(c) = stack.pop() {
+        if to.supports(c.0) {
+            v.push(c);
+        } else {
+            stack.extend(equivalent(c).into_iter().rev());
+        }
+    }
+    v
+}
+
+fn inner_slice(c: Command) -> Option<String> {
+    let face = match c.0 {
+        Move::M => "L",
+        Move::E => "D",
+        Move::S => "F",
+        _ => return None,
+    };
+    let mut s = format!("2{}", face);
+    if c.1.abs() == 2 {
+        s.push('2');
+    }
+    if c.1 < 0 {
+        s.push('\'');
+    }
+    Some(s)
+}
+
+/// Rewrite the algorithm and print it in the notation.
+pub fn format(cs: &[Command], to: Notation) -> String {
+    let cs = convert(cs, to);
+    let style = to.style();
+    match to {
+        Notation::Sign { inner_slices: true } => {
+            let v: Vec<String> = cs
+                .iter()
+                .map(|&c| inner_slice(c).unwrap_or_else(|| format_commands(&[c], &style)))
+                .collect();
+            v.join(" ")
+        }
+        _ => format_commands(&cs, &style),
+    }
+}
+
+#[cfg(test)]
+const NOTATIONS: [Notation; 5] = [
+    Notation::Singmaster,
+    Notation::Wca,
+    Notation::Sign {
+        inner_slices: false,
+    },
+    Notation::Sign { inner_slices: true },
+    Notation::CubingJs,
+];
+#[test]
+fn test_equivalent() {
+    use crate::matrix;
+    for mov in crate::MOVE_LIST {
+        for k in [-2, -1, 1, 2] {
+            let c = Command(mov, k);
+            assert_eq!(matrix::of_seq(&equivalent(c)), matrix::of_seq(&[c]));
+            for to in NOTATIONS {
+                let v = convert(&[c], to);
+                assert!(v.iter().all(|c| to.supports(c.0)));
+                assert_eq!(matrix::of_seq(&v), matrix::of_seq(&[c]));
+            }
+        }
+    }
+}
+#[test]
+fn test_format() {
+    use crate::flatten;
+    use crate::parser::parse;
+    let cs = flatten(parse("r U M' b2' x").unwrap().1);
+    assert_eq!(format(&cs, Notation::Wca), "Rw U x R' L Bw2 x");
+    assert_eq!(format(&cs, Notation::Singmaster), "R M' U M' B2 S2 x");
+    assert_eq!(
+        format(
+            &cs,
+            Notation::Sign {
+                inner_slices: false
+            }
+        ),
+        "r U M' b2' x"
+    );
+    assert_eq!(
+        format(&cs, Notation::Sign { inner_slices: true }),
+        "r U 2L' b2' x"
+    );
+    assert_eq!(format(&cs, Notation::CubingJs), "Rw U M' Bw2' x");
+}
+#[test]
+fn test_convert_random() {
+    use crate::flatten;
+    use crate::matrix;
+    use crate::parser::parse;
+    for _ in 0..100 {
+        let cs = crate::random(30);
+        let m = matrix::of_seq(&cs);
+        for to in NOTATIONS {
+            assert_eq!(matrix::of_seq(&convert(&cs, to)), m);
+            if to != (Notation::Sign { inner_slices: true }) {
+                let cs = flatten(parse(&format(&cs, to)).unwrap().1);
+                assert_eq!(matrix::of_seq(&cs), m);
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Lossless concrete syntax tree of a move sequence.
+//!
+//! Every token keeps its byte span in the source and its original spelling
+//! so the exact source text can be restored. This is useful for editor integrations.
+//! The tree can be lowered to `Elem`s to get the same result as `parser::parse`.
+
+use super::{parse_double, parse_move, parse_prime};
+use crate::{Command, Elem};
+
+use nom::branch::alt;
+use nom::character::complete::{char, multispace1};
+use nom::combinator::{all_consuming, consumed, map, opt, recognize};
+use nom::multi::{many0, many1};
+use nom::sequence::{pair, tuple};
+use nom::IResult;
+use std::ops::Range;
+
+/// A token with its byte span in the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub span: Range<usize>,
+    pub text: String,
+}
+
+/// Command like R2'
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandNode {
+    pub span: Range<usize>,
+    pub mov: Token,
+    pub double: Option<Token>,
+    pub prime: Option<Token>,
+}
+impl CommandNode {
+    pub fn command(&self) -> Command {
+        let mov = parse_move(&self.mov.text).unwrap().1;
+        Command(mov, rep_of(&self.double, &self.prime))
+    }
+}
+
+/// Group like (R U R')2
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupNode {
+    pub span: Range<usize>,
+    pub open: Token,
+    /// Commands and whitespaces in the parentheses.
+    pub children: Vec<Node>,
+    pub close: Token,
+    pub double: Option<Token>,
+    pub prime: Option<Token>,
+}
+impl GroupNode {
+    /// Commands in the parentheses.
+    pub fn commands(&self) -> Vec<Command> {
+        let mut v = vec![];
+        for x in &self.children {
+            if let Node::Command(c) = x {
+                v.push(c.command());
+            }
+        }
+        v
+    }
+    pub fn elem(&self) -> Elem {
+        Elem::Group(self.commands(), rep_of(&self.double, &self.prime))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Command(CommandNode),
+    Group(GroupNode),
+    Whitespace(Token),
+}
+impl Node {
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Node::Command(x) => x.span.clone(),
+            Node::Group(x) => x.span.clone(),
+            Node::Whitespace(x) => x.span.clone(),
+        }
+    }
+    /// All tokens in the source order.
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut v = vec![];
+        match self {
+            Node::Command(x) => {
+                v.push(&x.mov);
+                v.extend(x.double.iter());
+                v.extend(x.prime.iter());
+            }
+            Node::Group(x) => {
+                v.push(&x.open);
+                for child in &x.children {
+                    v.append(&mut child.tokens());
+                }
+                v.push(&x.close);
+                v.extend(x.double.iter());
+                v.extend(x.prime.iter());
+            }
+            Node::Whitespace(x) => v.push(x),
+        }
+        v
+    }
+}
+
+/// The syntax tree of a whole move sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cst {
+    pub nodes: Vec<Node>,
+}
+impl Cst {
+    /// Restore the source text.
+    pub fn to_source(&self) -> String {
+        let mut s = String::new();
+        for node in &self.nodes {
+            for tok in node.tokens() {
+                s.push_str(&tok.text);
+            }
+        }
+        s
+    }
+    /// Lower to the same representation as `parser::parse`.
+    pub fn lower(&self) -> Vec<Elem> {
+        let mut v = vec![];
+        for node in &self.nodes {
+            match node {
+                Node::Command(c) => v.push(Elem::One(c.command())),
+                Node::Group(g) => v.push(g.elem()),
+                Node::Whitespace(_) => {}
+            }
+        }
+        v
+    }
+    /// Find the innermost node containing the byte offset.
+    pub fn node_at(&self, offset: usize) -> Option<&Node> {
+        for node in &self.nodes {
+            if node.span().contains(&offset) {
+                if let Node::Group(g) = node {
+                    for child in &g.children {
+                        if child.span().contains(&offset) {
+                            return Some(child);
+                        }
+                    }
+                }
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+fn rep_of(double: &Option<Token>, prime: &Option<Token>) -> i8 {
+    let mut rep = 1;
+    if double.is_some() {
+        rep = 2;
+    }
+    if prime.is_some() {
+        rep *= -1;
+    }
+    rep
+}
+
+type Input<'a> = &'a str;
+
+/// Parser of tokens that knows the whole source to compute the spans.
+struct Ctx<'a> {
+    source: &'a str,
+}
+impl<'a> Ctx<'a> {
+    fn token(&self, s: &str) -> Token {
+        let start = s.as_ptr() as usize - self.source.as_ptr() as usize;
+        Token {
+            span: start..start + s.len(),
+            text: s.to_owned(),
+        }
+    }
+    fn span(&self, s: &str) -> Range<usize> {
+        self.token(s).span
+    }
+    fn whitespace(&self, i: Input<'a>) -> IResult<Input<'a>, Node> {
+        map(multispace1, |s| Node::Whitespace(self.token(s)))(i)
+    }
+    fn rep(&self, i: Input<'a>) -> IResult<Input<'a>, (Option<Token>, Option<Token>)> {
+        let double = map(opt(recognize(parse_double)), |s: Option<&str>| {
+            s.filter(|s| !s.is_empty()).map(|s| self.token(s))
+        });
+        let prime = map(opt(recognize(parse_prime)), |s: Option<&str>| {
+            s.filter(|s| !s.is_empty()).map(|s| self.token(s))
+        });
+        pair(double, prime)(i)
+    }
+    fn command(&self, i: Input<'a>) -> IResult<Input<'a>, CommandNode> {
+        let f = consumed(pair(recognize(parse_move), |i| self.rep(i)));
+        map(f, |(all, (mov, (double, prime)))| CommandNode {
+            span: self.span(all),
+            mov: self.token(mov),
+            double,
+            prime,
+        })(i)
+    }
+    fn group(&self, i: Input<'a>) -> IResult<Input<'a>, GroupNode> {
+        let body = pair(
+            many0(|i| self.whitespace(i)),
+            many1(pair(
+                map(|i| self.command(i), Node::Command),
+                many0(|i| self.whitespace(i)),
+            )),
+        );
+        let f = consumed(tuple((
+            recognize(char('(')),
+            body,
+            recognize(char(')')),
+            |i| self.rep(i),
+        )));
+        map(f, |(all, (open, (ws, body), close, (double, prime)))| {
+            let mut children = ws;
+            for (c, mut ws) in body {
+                children.push(c);
+                children.append(&mut ws);
+            }
+            GroupNode {
+                span: self.span(all),
+                open: self.token(open),
+                children,
+                close: self.token(close),
+                double,
+                prime,
+            }
+        })(i)
+    }
+    fn node(&self, i: Input<'a>) -> IResult<Input<'a>, Node> {
+        alt((
+            map(|i| self.command(i), Node::Command),
+            map(|i| self.group(i), Node::Group),
+            |i| self.whitespace(i),
+        ))(i)
+    }
+}
+
+pub fn parse(i: &str) -> IResult<&str, Cst> {
+    let ctx = Ctx { source: i };
+    let p = many0(move |i| ctx.node(i));
+    map(all_consuming(p), |nodes| Cst { nodes })(i)
+}
+
+#[test]
+fn test_parse() {
+    let s = " (R2' U)2 Rw\n";
+    let cst = parse(s).unwrap().1;
+    assert_eq!(cst.to_source(), s);
+    assert_eq!(cst.lower(), super::parse(s).unwrap().1);
+    assert_eq!(cst.nodes.len(), 5);
+    match &cst.nodes[1] {
+        Node::Group(g) => {
+            assert_eq!(g.span, 1..9);
+            assert_eq!(g.children.len(), 3);
+            assert_eq!(g.double.as_ref().unwrap().span, 8..9);
+            assert_eq!(g.prime, None);
+        }
+        _ => panic!(),
+    }
+    match &cst.nodes[3] {
+        Node::Command(c) => {
+            assert_eq!(c.span, 10..12);
+            assert_eq!(c.mov.text, "Rw");
+            assert_eq!(c.command(), Command(crate::Move::r, 1));
+        }
+        _ => panic!(),
+    }
+    assert!(parse("(R").is_err());
+    assert!(parse("R'2").is_err());
+}
+#[test]
+fn test_node_at() {
+    let s = "R (U F')";
+    let cst = parse(s).unwrap().1;
+    assert!(matches!(cst.node_at(0), Some(Node::Command(_))));
+    assert!(matches!(cst.node_at(1), Some(Node::Whitespace(_))));
+    assert!(matches!(cst.node_at(2), Some(Node::Group(_))));
+    match cst.node_at(5) {
+        Some(Node::Command(c)) => assert_eq!(&s[c.span.clone()], "F'"),
+        _ => panic!(),
+    }
+    assert_eq!(cst.node_at(8), None);
+}
+#[test]
+fn test_invert_group() {
+    use crate::format::{format_elem, Style};
+    let s = "R (U F') D";
+    let cst = parse(s).unwrap().1;
+    let g = match cst.node_at(2) {
+        Some(Node::Group(g)) => g,
+        _ => panic!(),
+    };
+    let inv = match g.elem() {
+        Elem::Group(cs, rep) => Elem::Group(cs, -rep),
+        _ => unreachable!(),
+    };
+    let mut t = s.to_owned();
+    t.replace_range(g.span.clone(), &format_elem(&inv, &Style::default()));
+    assert_eq!(t, "R (U F')' D");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn arb_token() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[RLUDFBrludfbMESxyz]2?'?",
+            "[RLUDFB]w2?'?",
+            "\\(( ?[RLUDFB]2?'?)+ ?\\)2?'?",
+            "[ \n\t]{1,3}",
+        ]
+    }
+    proptest! {
+        #[test]
+        fn test_roundtrip(toks in prop::collection::vec(arb_token(), 0..30)) {
+            let s = toks.concat();
+            let expected = super::super::parse(&s);
+            let cst = parse(&s);
+            assert_eq!(cst.is_ok(), expected.is_ok());
+            if let Ok((_, cst)) = cst {
+                assert_eq!(cst.to_source(), s);
+                assert_eq!(cst.lower(), expected.unwrap().1);
+                for node in &cst.nodes {
+                    for tok in node.tokens() {
+                        assert_eq!(&s[tok.span.clone()], tok.text);
+                    }
+                }
+            }
+        }
+    }
+}
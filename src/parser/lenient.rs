@@ -0,0 +1,248 @@
+//! Lenient parsing of algorithms copy-pasted from websites and chats.
+//!
+//! The input is normalized into the canonical notation before parsing
+//! and every substitution is reported so nothing is misread silently.
+//!
+//! Normalization:
+//! - Typographic primes like ’ ′ ʼ are replaced by '
+//! - Inverse suffix like Ri is replaced by R'
+//! - Full-width letters like Ｒ are replaced by R
+//! - Mixed case wide moves like rw are replaced by Rw
+//! - Trailing punctuations like . are removed
+
+use crate::Elem;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    TypographicPrime,
+    InverseSuffix,
+    FullWidth,
+    WideCase,
+    TrailingPunctuation,
+}
+
+/// A substitution made during normalization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Substitution {
+    /// Byte span in the original input.
+    pub span: Range<usize>,
+    pub from: String,
+    pub to: String,
+    pub reason: Reason,
+}
+
+/// The normalized input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Normalized {
+    pub text: String,
+    pub substitutions: Vec<Substitution>,
+    /// The byte offset in the original input for each byte in `text` plus the end.
+    offsets: Vec<usize>,
+}
+impl Normalized {
+    /// Map a byte offset in the normalized text to the offset in the original input.
+    pub fn original_offset(&self, i: usize) -> usize {
+        self.offsets[i]
+    }
+}
+
+/// Error of lenient parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    /// Byte offset in the original input where the parsing failed.
+    pub offset: usize,
+}
+
+/// Result of lenient parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lenient {
+    pub elems: Vec<Elem>,
+    pub substitutions: Vec<Substitution>,
+}
+
+const PRIMES: [char; 7] = ['’', '‘', '′', 'ʼ', '´', '`', '＇'];
+const PUNCTUATIONS: [char; 6] = ['.', ',', ';', ':', '!', '。'];
+
+fn full_width(c: char) -> Option<char> {
+    match c as u32 {
+        0xFF01..=0xFF5E => std::char::from_u32(c as u32 - 0xFEE0),
+        0x3000 => Some(' '),
+        _ => None,
+    }
+}
+fn is_face(c: char) -> bool {
+    "RLUDFB".contains(c.to_ascii_uppercase()) && c.is_ascii_alphabetic()
+}
+
+struct Char {
+    c: char,
+    span: Range<usize>,
+}
+
+/// Normalize the input into the canonical notation.
+pub fn normalize(i: &str) -> Normalized {
+    let mut subs = vec![];
+    let mut chars: Vec<Char> = vec![];
+    for (k, c) in i.char_indices() {
+        let span = k..k + c.len_utf8();
+        let mut to = c;
+        if PRIMES.contains(&c) {
+            to = '\'';
+            subs.push(Substitution {
+                span: span.clone(),
+                from: c.to_string(),
+                to: to.to_string(),
+                reason: Reason::TypographicPrime,
+            });
+        } else if let Some(x) = full_width(c) {
+            to = x;
+            subs.push(Substitution {
+                span: span.clone(),
+                from: c.to_string(),
+                to: to.to_string(),
+                reason: Reason::FullWidth,
+            });
+        }
+        chars.push(Char { c: to, span });
+    }
+
+    let n = chars.len();
+    for k in 0..n {
+        let prev = if k > 0 { Some(chars[k - 1].c) } else { None };
+        let cur = chars[k].c;
+        // rw, RW or rW
+        if (cur == 'w' || cur == 'W') && prev.map(is_face) == Some(true) {
+            let p = prev.unwrap();
+            if p.is_ascii_lowercase() || cur == 'W' {
+                let span = chars[k - 1].span.start..chars[k].span.end;
+                subs.push(Substitution {
+                    from: i[span.clone()].to_owned(),
+                    span,
+                    to: format!("{}w", p.to_ascii_uppercase()),
+                    reason: Reason::WideCase,
+                });
+                chars[k - 1].c = p.to_ascii_uppercase();
+                chars[k].c = 'w';
+            }
+        }
+        // Ri or R2i
+        if cur == 'i' && prev.map(|p| p.is_ascii_alphabetic() || p == '2' || p == ')') == Some(true)
+        {
+            subs.push(Substitution {
+                span: chars[k].span.clone(),
+                from: i[chars[k].span.clone()].to_owned(),
+                to: "'".to_owned(),
+                reason: Reason::InverseSuffix,
+            });
+            chars[k].c = '\'';
+        }
+    }
+
+    let mut end = n;
+    while end > 0 && (chars[end - 1].c.is_whitespace() || PUNCTUATIONS.contains(&chars[end - 1].c))
+    {
+        end -= 1;
+    }
+    for x in &chars[end..] {
+        if PUNCTUATIONS.contains(&x.c) {
+            subs.push(Substitution {
+                span: x.span.clone(),
+                from: i[x.span.clone()].to_owned(),
+                to: "".to_owned(),
+                reason: Reason::TrailingPunctuation,
+            });
+        }
+    }
+
+    let mut text = String::new();
+    let mut offsets = vec![];
+    for x in &chars[..end] {
+        for _ in 0..x.c.len_utf8() {
+            offsets.push(x.span.start);
+        }
+        text.push(x.c);
+    }
+    offsets.push(chars[..end].last().map(|x| x.span.end).unwrap_or(0));
+    subs.sort_by_key(|s| (s.span.start, s.span.end));
+    Normalized {
+        text,
+        substitutions: subs,
+        offsets,
+    }
+}
+
+/// Normalize the input and parse it.
+pub fn parse(i: &str) -> Result<Lenient, Error> {
+    let norm = normalize(i);
+    match super::parse(&norm.text) {
+        Ok((_, elems)) => Ok(Lenient {
+            elems,
+            substitutions: norm.substitutions,
+        }),
+        Err(e) => {
+            let rest = match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+                nom::Err::Incomplete(_) => "",
+            };
+            let k = norm.text.len() - rest.len();
+            Err(Error {
+                offset: norm.original_offset(k),
+            })
+        }
+    }
+}
+
+#[test]
+fn test_parse() {
+    let canonical = super::parse("RUR'U'r2'(Rw U)").unwrap().1;
+    let x = parse("ＲU R’ Uⅰ").unwrap_err();
+    assert_eq!(x.offset, 11);
+
+    let x = parse("ＲUR’Ui rw2′(RW U).").unwrap();
+    assert_eq!(x.elems, canonical);
+    let reasons: Vec<Reason> = x.substitutions.iter().map(|s| s.reason).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            Reason::FullWidth,
+            Reason::TypographicPrime,
+            Reason::InverseSuffix,
+            Reason::WideCase,
+            Reason::TypographicPrime,
+            Reason::WideCase,
+            Reason::TrailingPunctuation,
+        ]
+    );
+    assert_eq!(
+        x.substitutions[0],
+        Substitution {
+            span: 0..3,
+            from: "Ｒ".to_owned(),
+            to: "R".to_owned(),
+            reason: Reason::FullWidth,
+        }
+    );
+    assert_eq!(x.substitutions[3].from, "rw");
+    assert_eq!(x.substitutions[3].to, "Rw");
+}
+#[test]
+fn test_strict_input() {
+    for (_, seq) in crate::cfop::PLL_LIST {
+        let x = parse(seq).unwrap();
+        assert_eq!(x.elems, super::parse(seq).unwrap().1);
+        assert!(x.substitutions.is_empty());
+    }
+    // r is a wide move. Only rw is normalized.
+    let x = parse("r U r'").unwrap();
+    assert!(x.substitutions.is_empty());
+}
+#[test]
+fn test_error_offset() {
+    let x = parse("R U’ X").unwrap_err();
+    assert_eq!(x.offset, 7);
+    let x = parse("R U (").unwrap_err();
+    assert_eq!(x.offset, 4);
+    assert_eq!(parse("").unwrap().elems, vec![]);
+    assert_eq!(parse(" ..").unwrap().substitutions.len(), 2);
+}
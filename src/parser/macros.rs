@@ -0,0 +1,419 @@
+//! User-defined algorithm macros like {sexy}3
+//!
+//! Macros are defined in an environment like `sexy = R U R' U'`
+//! and referenced by name in algorithms. A macro can reference other macros.
+//! `parser::parse_with` parses an algorithm with the macros of an environment.
+//! `parser::parse` doesn't know macros because it has no environment.
+//!
+//! Syntax:
+//! - Name -> [A-Za-z_][A-Za-z0-9_]*
+//! - Count -> [0-9]+ | ε
+//! - Macro -> { Name } Count Prime
+//! - Item -> Macro | Elem
+//! - Seq -> Item*
+//! - Def -> Name = Seq
+
+use super::{parse_elem, parse_prime};
+use crate::format::{format_elem, Style};
+use crate::{flatten, Command, Elem};
+
+use nom::branch::alt;
+use nom::bytes::complete::take_while;
+use nom::character::complete::{char, multispace0, satisfy, u8 as parse_u8};
+use nom::combinator::{all_consuming, map, map_opt, opt, recognize};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use nom::IResult;
+use std::collections::HashMap;
+
+/// Element of a sequence with macros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    Elem(Elem),
+    /// Macro name and repeatance.
+    Macro(String, i8),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The definition can't be parsed.
+    Syntax(String),
+    Undefined(String),
+    /// Recursive definition. The names in the cycle.
+    Cycle(Vec<String>),
+}
+
+fn parse_name(i: &str) -> IResult<&str, &str> {
+    let head = satisfy(|c| c.is_ascii_alphabetic() || c == '_');
+    let tail = take_while(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    recognize(pair(head, tail))(i)
+}
+fn parse_count(i: &str) -> IResult<&str, i8> {
+    let f = map_opt(opt(parse_u8), |n| match n {
+        None => Some(1),
+        Some(n) if n > 0 && n <= 127 => Some(n as i8),
+        _ => None,
+    });
+    let f = pair(f, parse_prime);
+    map(f, |(n, prime)| if prime { -n } else { n })(i)
+}
+fn parse_macro(i: &str) -> IResult<&str, Item> {
+    let name = delimited(char('{'), parse_name, char('}'));
+    map(pair(name, parse_count), |(name, n)| {
+        Item::Macro(name.to_owned(), n)
+    })(i)
+}
+fn parse_item(i: &str) -> IResult<&str, Item> {
+    alt((parse_macro, map(parse_elem, Item::Elem)))(i)
+}
+fn parse_items(i: &str) -> IResult<&str, Vec<Item>> {
+    preceded(multispace0, many0(terminated(parse_item, multispace0)))(i)
+}
+
+pub fn parse(i: &str) -> IResult<&str, Vec<Item>> {
+    all_consuming(parse_items)(i)
+}
+
+/// Set of macro definitions.
+#[derive(Clone, Debug, Default)]
+pub struct Env {
+    defs: HashMap<String, Vec<Item>>,
+    /// The expansion of every macro. Updated when a macro is defined.
+    expanded: HashMap<String, Vec<Command>>,
+}
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Parse definitions like `sexy = R U R' U'` one per line.
+    ///
+    /// Empty lines are ignored.
+    pub fn parse(defs: &str) -> Result<Self, Error> {
+        let mut env = Self::new();
+        for line in defs.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let def = separated_pair(
+                preceded(multispace0, parse_name),
+                tuple((multispace0, char('='))),
+                parse_items,
+            );
+            let (_, (name, items)) =
+                all_consuming(def)(line).map_err(|_| Error::Syntax(line.to_owned()))?;
+            env.defs.insert(name.to_owned(), items);
+        }
+        env.expanded = env.expand_all()?;
+        Ok(env)
+    }
+    /// Define a macro. Redefinition overwrites the old one.
+    pub fn define(&mut self, name: &str, alg: &str) -> Result<(), Error> {
+        if all_consuming(parse_name)(name).is_err() {
+            return Err(Error::Syntax(name.to_owned()));
+        }
+        let items = parse(alg).map_err(|_| Error::Syntax(alg.to_owned()))?.1;
+        let old = self.defs.insert(name.to_owned(), items);
+        match self.expand_all() {
+            Ok(expanded) => self.expanded = expanded,
+            Err(e) => {
+                match old {
+                    Some(old) => self.defs.insert(name.to_owned(), old),
+                    None => self.defs.remove(name),
+                };
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    pub fn get(&self, name: &str) -> Option<&[Item]> {
+        self.defs.get(name).map(|x| x.as_slice())
+    }
+    /// Check that every macro can be expanded.
+    pub fn check(&self) -> Result<(), Error> {
+        self.expand_all().map(|_| ())
+    }
+    /// Expand every macro. Each macro is expanded only once.
+    fn expand_all(&self) -> Result<HashMap<String, Vec<Command>>, Error> {
+        let mut names: Vec<&String> = self.defs.keys().collect();
+        names.sort();
+        let mut memo = HashMap::new();
+        for name in names {
+            self.expand_macro(name, &mut vec![], &mut memo)?;
+        }
+        Ok(memo)
+    }
+    fn expand_macro(
+        &self,
+        name: &str,
+        stack: &mut Vec<String>,
+        memo: &mut HashMap<String, Vec<Command>>,
+    ) -> Result<Vec<Command>, Error> {
+        if let Some(cs) = memo.get(name) {
+            return Ok(cs.clone());
+        }
+        if let Some(k) = stack.iter().position(|x| x == name) {
+            let mut cycle = stack[k..].to_vec();
+            cycle.push(name.to_owned());
+            return Err(Error::Cycle(cycle));
+        }
+        let items = self
+            .defs
+            .get(name)
+            .ok_or_else(|| Error::Undefined(name.to_owned()))?;
+        stack.push(name.to_owned());
+        let v = flatten_items(items, &mut |name| self.expand_macro(name, stack, memo))?;
+        stack.pop();
+        memo.insert(name.to_owned(), v.clone());
+        Ok(v)
+    }
+    fn lookup(&self, name: &str) -> Result<Vec<Command>, Error> {
+        self.expanded
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::Undefined(name.to_owned()))
+    }
+    /// Turn a sequence with macros into a sequence without parentheses.
+    pub fn flatten(&self, items: &[Item]) -> Result<Vec<Command>, Error> {
+        flatten_items(items, &mut |name| self.lookup(name))
+    }
+    /// Replace macros by groups.
+    ///
+    /// A group repeats only once or twice like in `parse`
+    /// so `{sexy}3` is `(R U R' U')2 (R U R' U')`.
+    /// Empty macros are dropped.
+    pub fn expand(&self, items: &[Item]) -> Result<Vec<Elem>, Error> {
+        let mut v = vec![];
+        for item in items {
+            match item {
+                Item::Elem(e) => v.push(e.clone()),
+                Item::Macro(name, rep) => {
+                    let cs = self.lookup(name)?;
+                    if cs.is_empty() {
+                        continue;
+                    }
+                    let sign = rep.signum();
+                    for _ in 0..rep.abs() / 2 {
+                        v.push(Elem::Group(cs.clone(), 2 * sign));
+                    }
+                    if rep % 2 != 0 {
+                        v.push(Elem::Group(cs, sign));
+                    }
+                }
+            }
+        }
+        Ok(v)
+    }
+    /// Abbreviate the known macros in the sequence.
+    ///
+    /// Longer macros are preferred and consecutive repetitions are counted.
+    /// A macro is preferred to the inverse of another macro of the same length.
+    pub fn abbreviate(&self, cs: &[Command]) -> Vec<Item> {
+        let mut pats = vec![];
+        for (name, body) in &self.expanded {
+            if !body.is_empty() {
+                let inv: Vec<Command> = body.iter().rev().map(|c| c.prime()).collect();
+                pats.push((name, body.clone(), 1));
+                pats.push((name, inv, -1));
+            }
+        }
+        pats.sort_by(|a, b| {
+            b.1.len()
+                .cmp(&a.1.len())
+                .then(b.2.cmp(&a.2))
+                .then(a.0.cmp(b.0))
+        });
+
+        let mut v = vec![];
+        let mut i = 0;
+        'outer: while i < cs.len() {
+            for (name, pat, sign) in &pats {
+                let mut n = 0;
+                while n < 127 && cs[i + n as usize * pat.len()..].starts_with(pat) {
+                    n += 1;
+                }
+                if n > 0 {
+                    v.push(Item::Macro(name.to_string(), sign * n));
+                    i += n as usize * pat.len();
+                    continue 'outer;
+                }
+            }
+            v.push(Item::Elem(Elem::One(cs[i])));
+            i += 1;
+        }
+        v
+    }
+}
+
+fn flatten_items(
+    items: &[Item],
+    expand: &mut impl FnMut(&str) -> Result<Vec<Command>, Error>,
+) -> Result<Vec<Command>, Error> {
+    let mut v = vec![];
+    for item in items {
+        match item {
+            Item::Elem(e) => v.append(&mut flatten(vec![e.clone()])),
+            Item::Macro(name, rep) => {
+                let cs = expand(name)?;
+                v.append(&mut flatten(vec![Elem::Group(cs, *rep)]));
+            }
+        }
+    }
+    Ok(v)
+}
+
+/// Format a sequence with macros.
+pub fn format(items: &[Item], style: &Style) -> String {
+    let mut v = vec![];
+    for item in items {
+        match item {
+            Item::Elem(e) => v.push(format_elem(e, style)),
+            Item::Macro(name, rep) => {
+                let mut s = format!("{{{}}}", name);
+                if rep.abs() != 1 {
+                    s.push_str(&rep.abs().to_string());
+                }
+                if *rep < 0 {
+                    s.push('\'');
+                }
+                v.push(s);
+            }
+        }
+    }
+    let sep = if style.spacing { " " } else { "" };
+    v.join(sep)
+}
+
+#[cfg(test)]
+fn env() -> Env {
+    Env::parse(
+        "sexy = R U R' U'
+         sledge = R' F R F'
+
+         hedge = {sledge}'
+         double = {sexy}2",
+    )
+    .unwrap()
+}
+#[test]
+fn test_parse() {
+    use crate::Move::*;
+    let items = parse("{sexy}3 R (U){sledge}' {a_1}2'").unwrap().1;
+    assert_eq!(
+        items,
+        vec![
+            Item::Macro("sexy".to_owned(), 3),
+            Item::Elem(Elem::One(Command(R, 1))),
+            Item::Elem(Elem::Group(vec![Command(U, 1)], 1)),
+            Item::Macro("sledge".to_owned(), -1),
+            Item::Macro("a_1".to_owned(), -2),
+        ]
+    );
+    assert!(parse("{sexy}0").is_err());
+    assert!(parse("{sexy}200").is_err());
+    assert!(parse("{1a}").is_err());
+    assert!(parse("{}").is_err());
+    assert!(Env::parse("x = R\n= U").is_err());
+}
+#[test]
+fn test_flatten() {
+    use crate::cfop::solved;
+    use crate::matrix;
+    let env = env();
+    let sexy = super::parse("RUR'U'").unwrap().1;
+    let items = parse("{sexy}3").unwrap().1;
+    let expected = flatten(vec![
+        Elem::Group(flatten(sexy), 1),
+        Elem::Group(flatten(super::parse("RUR'U'").unwrap().1), 2),
+    ]);
+    assert_eq!(env.flatten(&items).unwrap(), expected);
+    assert!(solved(&matrix::of_seq(
+        &env.flatten(&parse("{sexy}6").unwrap().1).unwrap()
+    )));
+    assert!(solved(&matrix::of_seq(
+        &env.flatten(&parse("{double}3").unwrap().1).unwrap()
+    )));
+    assert!(solved(&matrix::of_seq(
+        &env.flatten(&parse("{sledge}{hedge}").unwrap().1).unwrap()
+    )));
+    assert_eq!(
+        env.expand(&parse("{sledge}'").unwrap().1).unwrap(),
+        super::parse("(R'FRF')'").unwrap().1
+    );
+    // The expansion can be parsed back.
+    let mut env = env;
+    env.define("empty", "").unwrap();
+    let items = parse("{sexy}3 {double}5' {empty}2 U").unwrap().1;
+    let elems = env.expand(&items).unwrap();
+    assert_eq!(elems.len(), 6);
+    let s = crate::format::format(&elems, &Style::default());
+    assert_eq!(
+        flatten(super::parse(&s).unwrap().1),
+        env.flatten(&items).unwrap()
+    );
+    assert_eq!(
+        env.flatten(&parse("{nothing}").unwrap().1),
+        Err(Error::Undefined("nothing".to_owned()))
+    );
+}
+#[test]
+fn test_parse_with() {
+    let env = env();
+    let elems = super::parse_with("{sexy}3 U", &env).unwrap();
+    assert_eq!(
+        flatten(elems),
+        env.flatten(&parse("{sexy}3 U").unwrap().1).unwrap()
+    );
+    assert!(super::parse_with("{sexy", &env).is_err());
+    assert_eq!(
+        super::parse_with("{nothing}", &env),
+        Err(Error::Undefined("nothing".to_owned()))
+    );
+}
+#[test]
+fn test_nested() {
+    // Each level doubles the length.
+    let mut env = Env::new();
+    env.define("m0", "R").unwrap();
+    for i in 1..=20 {
+        env.define(&format!("m{}", i), &format!("{{m{}}}2", i - 1))
+            .unwrap();
+    }
+    let cs = env.flatten(&parse("{m20}").unwrap().1).unwrap();
+    assert_eq!(cs.len(), 1 << 20);
+}
+#[test]
+fn test_cycle() {
+    let e = Env::parse("a = R {b}\nb = {c}\nc = U {a}2").unwrap_err();
+    assert_eq!(
+        e,
+        Error::Cycle(vec![
+            "a".to_owned(),
+            "b".to_owned(),
+            "c".to_owned(),
+            "a".to_owned()
+        ])
+    );
+    let mut env = env();
+    assert!(env.define("sexy", "{double}").is_err());
+    // The old definition is kept.
+    assert_eq!(env.get("sexy").unwrap().len(), 4);
+    assert!(env.define("self", "{self}").is_err());
+    assert!(env.get("self").is_none());
+    assert!(env.define("sune", "R U R' U R U2 R'").is_ok());
+}
+#[test]
+fn test_abbreviate() {
+    let env = env();
+    let cs = flatten(super::parse("RUR'U'RUR'U'R'FRF'FR'F'RLURU'R'U'").unwrap().1);
+    let items = env.abbreviate(&cs);
+    assert_eq!(
+        format(&items, &Style::default()),
+        "{double} {sledge} {hedge} L {sexy}' U'"
+    );
+    assert_eq!(env.flatten(&items).unwrap(), cs);
+    for _ in 0..100 {
+        let cs = crate::random(50);
+        let s = format(&env.abbreviate(&cs), &Style::compact());
+        assert_eq!(env.flatten(&parse(&s).unwrap().1).unwrap(), cs);
+    }
+}
@@ -1,7 +1,7 @@
 //! Parse rotation sequence like (RUR')U'(R'FR)F'
 //!
 //! Syntax:
-//! - Move -> R|L|U|D|F|B| ...
+//! - Move -> R|L|U|D|F|B| ... | Rw|Lw|Uw|Dw|Fw|Bw
 //! - Double -> 2 | ε
 //! - Prime -> ' | ε
 //! - Rep -> Double Prime
@@ -9,17 +9,37 @@
 //! - Group -> ( Command+ ) Rep
 //! - Elem -> Command | Group
 //! - Seq -> Elem+
+//!
+//! Whitespaces are allowed between commands so `R U R' U'` is also accepted.
 
 use crate::{Command, Elem, Move};
 
+pub mod cst;
+pub mod lenient;
+pub mod macros;
+pub mod niss;
+pub mod timed;
+
 use nom::branch::alt;
-use nom::character::complete::{char, one_of};
+use nom::character::complete::{char, multispace0, one_of};
 use nom::combinator::{all_consuming, map};
 use nom::multi::{many0, many1, many_m_n};
-use nom::sequence::{delimited, pair};
+use nom::sequence::{delimited, pair, preceded, terminated};
 use nom::IResult;
 
-fn parse_move(i: &str) -> IResult<&str, Move> {
+fn parse_wide(i: &str) -> IResult<&str, Move> {
+    let f = terminated(one_of("RLUDFB"), char('w'));
+    map(f, |c| match c {
+        'R' => Move::r,
+        'L' => Move::l,
+        'U' => Move::u,
+        'D' => Move::d,
+        'F' => Move::f,
+        'B' => Move::b,
+        _ => unreachable!(),
+    })(i)
+}
+fn parse_single(i: &str) -> IResult<&str, Move> {
     map(one_of("RLUDFBrludfbMESxyz"), |c| match c {
         'R' => Move::R,
         'L' => Move::L,
@@ -42,6 +62,9 @@ fn parse_move(i: &str) -> IResult<&str, Move> {
         _ => unreachable!(),
     })(i)
 }
+fn parse_move(i: &str) -> IResult<&str, Move> {
+    alt((parse_wide, parse_single))(i)
+}
 fn parse_double(i: &str) -> IResult<&str, bool> {
     map(many_m_n(0, 1, char('2')), |v| v.len() > 0)(i)
 }
@@ -66,8 +89,8 @@ fn parse_command(i: &str) -> IResult<&str, Command> {
     map(f, |(mov, rep)| Command(mov, rep.0))(i)
 }
 fn parse_group(i: &str) -> IResult<&str, (Vec<Command>, i8)> {
-    let p1 = char('(');
-    let p2 = many1(parse_command);
+    let p1 = terminated(char('('), multispace0);
+    let p2 = many1(terminated(parse_command, multispace0));
     let p3 = char(')');
     let f = delimited(p1, p2, p3);
     let f = pair(f, parse_rep);
@@ -80,9 +103,68 @@ fn parse_elem(i: &str) -> IResult<&str, Elem> {
 }
 
 pub fn parse(i: &str) -> IResult<&str, Vec<Elem>> {
-    let p = many0(parse_elem);
+    let p = preceded(multispace0, many0(terminated(parse_elem, multispace0)));
     all_consuming(p)(i)
 }
+
+/// `parse` with the macros of the environment like `{sexy}3`.
+///
+/// The macros are expanded into groups by `macros::Env::expand`
+/// so the result can be passed to `flatten` as usual.
+pub fn parse_with(i: &str, env: &macros::Env) -> Result<Vec<Elem>, macros::Error> {
+    let items = macros::parse(i)
+        .map_err(|_| macros::Error::Syntax(i.to_owned()))?
+        .1;
+    env.expand(&items)
+}
+
+/// Iterator version of `parse` that parses the elements one by one.
+///
+/// The iteration stops after an error is returned.
+pub fn parse_iter(i: &str) -> ParseIter<'_> {
+    ParseIter { rest: Some(i) }
+}
+/// Iterator returned by `parse_iter`.
+pub struct ParseIter<'a> {
+    rest: Option<&'a str>,
+}
+impl<'a> Iterator for ParseIter<'a> {
+    type Item = Result<Elem, nom::Err<nom::error::Error<&'a str>>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.rest?;
+        let (i, _) = multispace0::<_, nom::error::Error<_>>(i).ok()?;
+        if i.is_empty() {
+            self.rest = None;
+            return None;
+        }
+        match parse_elem(i) {
+            Ok((i, e)) => {
+                self.rest = Some(i);
+                Some(Ok(e))
+            }
+            Err(e) => {
+                self.rest = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+#[test]
+fn test_parse_iter() {
+    for s in [
+        "",
+        " R U2' ",
+        "(RU')(RU)2(RU')R'U'R2",
+        "R2D(R'U2R)D'(R'U2R')",
+    ] {
+        let v: Result<Vec<Elem>, _> = parse_iter(s).collect();
+        assert_eq!(v.unwrap(), parse(s).unwrap().1);
+    }
+    let v: Vec<_> = parse_iter("R U N D").collect();
+    assert_eq!(v.len(), 3);
+    assert!(v[2].is_err());
+    assert!(parse_iter("R 2").any(|x| x.is_err()));
+}
 #[test]
 fn test_parse() {
     use Elem::*;
@@ -101,3 +183,19 @@ fn test_parse() {
     assert!(parse("(R2'U)(RUR')(U'R'U')(R'UR')").is_ok());
     assert!(parse("RNA").is_err());
 }
+#[test]
+fn test_parse_spaces() {
+    assert_eq!(parse("R U R' U'").unwrap().1, parse("RUR'U'").unwrap().1);
+    assert_eq!(
+        parse(" (R U R')2 \n U' ").unwrap().1,
+        parse("(RUR')2U'").unwrap().1
+    );
+    assert!(parse("R 2").is_err());
+    assert!(parse("( )").is_err());
+}
+#[test]
+fn test_parse_wide() {
+    assert_eq!(parse("Rw2'").unwrap().1, parse("r2'").unwrap().1);
+    assert_eq!(parse("(Fw U)").unwrap().1, parse("(fU)").unwrap().1);
+    assert!(parse("Mw").is_err());
+}
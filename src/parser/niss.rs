@@ -0,0 +1,133 @@
+//! Parse fewest-moves solutions written with NISS like R U (F' D2) L
+//!
+//! In this mode, parentheses mean the moves are done on the inverse scramble
+//! so they can't be used for grouping.
+//!
+//! Syntax:
+//! - Inverse -> ( Command+ )
+//! - Elem -> Command | Inverse
+//! - Seq -> Elem*
+
+use super::parse_command;
+use crate::Command;
+
+use nom::branch::alt;
+use nom::character::complete::{char, multispace0};
+use nom::combinator::{all_consuming, map};
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, terminated};
+use nom::IResult;
+
+/// Solution split into the moves on the normal scramble
+/// and the moves on the inverse scramble.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Niss {
+    pub normal: Vec<Command>,
+    pub inverse: Vec<Command>,
+}
+impl Niss {
+    /// Combine both sides into a single solution for the normal scramble.
+    ///
+    /// The moves on the inverse side are inverted and appended to the moves on the normal side.
+    pub fn combine(&self) -> Vec<Command> {
+        let mut v = self.normal.clone();
+        for c in self.inverse.iter().rev() {
+            v.push(c.prime());
+        }
+        v
+    }
+    /// Combine with a scramble into a single sequence that solves the cube.
+    pub fn apply(&self, scramble: &[Command]) -> Vec<Command> {
+        let mut v = scramble.to_vec();
+        v.append(&mut self.combine());
+        v
+    }
+}
+
+enum Side {
+    Normal(Command),
+    Inverse(Vec<Command>),
+}
+fn parse_inverse(i: &str) -> IResult<&str, Vec<Command>> {
+    let p1 = terminated(char('('), multispace0);
+    let p2 = many1(terminated(parse_command, multispace0));
+    let p3 = char(')');
+    delimited(p1, p2, p3)(i)
+}
+fn parse_side(i: &str) -> IResult<&str, Side> {
+    let p1 = map(parse_command, Side::Normal);
+    let p2 = map(parse_inverse, Side::Inverse);
+    alt((p1, p2))(i)
+}
+
+pub fn parse(i: &str) -> IResult<&str, Niss> {
+    let p = preceded(multispace0, many0(terminated(parse_side, multispace0)));
+    let p = map(p, |sides| {
+        let mut niss = Niss::default();
+        for side in sides {
+            match side {
+                Side::Normal(c) => niss.normal.push(c),
+                Side::Inverse(mut cs) => niss.inverse.append(&mut cs),
+            }
+        }
+        niss
+    });
+    all_consuming(p)(i)
+}
+#[test]
+fn test_parse() {
+    use crate::Move::*;
+    let niss = parse("R U (F' D2) L (B)").unwrap().1;
+    assert_eq!(
+        niss.normal,
+        vec![Command(R, 1), Command(U, 1), Command(L, 1)]
+    );
+    assert_eq!(
+        niss.inverse,
+        vec![Command(F, -1), Command(D, 2), Command(B, 1)]
+    );
+    assert_eq!(
+        niss.combine(),
+        vec![
+            Command(R, 1),
+            Command(U, 1),
+            Command(L, 1),
+            Command(B, -1),
+            Command(D, -2),
+            Command(F, 1)
+        ]
+    );
+    assert_eq!(parse("").unwrap().1, Niss::default());
+    assert!(parse("(R U)2").is_err());
+    assert!(parse("(R (U))").is_err());
+    assert!(parse("()").is_err());
+}
+#[test]
+fn test_solve() {
+    use crate::cfop::solved;
+    use crate::format::{format_commands, Style};
+    use crate::matrix;
+    for _ in 0..100 {
+        let scramble = crate::random(20);
+        let mut sol: Vec<Command> = scramble.iter().rev().map(|c| c.prime()).collect();
+        // The latter half is found on the inverse scramble.
+        let latter: Vec<Command> = sol.split_off(10).iter().rev().map(|c| c.prime()).collect();
+        let s = format!(
+            "{} ({})",
+            format_commands(&sol, &Style::default()),
+            format_commands(&latter, &Style::default())
+        );
+        let niss = parse(&s).unwrap().1;
+        assert_eq!(niss.normal, sol);
+        assert_eq!(niss.inverse, latter);
+
+        let m = matrix::of_seq(&niss.apply(&scramble));
+        assert!(solved(&m));
+
+        // The inverse moves solve the inverse scramble after the normal moves are applied.
+        let mut inv: Vec<Command> = niss.normal.iter().rev().map(|c| c.prime()).collect();
+        inv.extend(scramble.iter().rev().map(|c| c.prime()));
+        inv.extend(niss.inverse.iter().copied());
+        assert!(solved(&matrix::of_seq(&inv)));
+    }
+}
@@ -0,0 +1,222 @@
+//! Parse timestamped move streams like R@120 U@250 R'@380
+//!
+//! Smart cubes and video reconstructions record the time of each move
+//! in milliseconds. Timestamps should be non-decreasing.
+//!
+//! Syntax:
+//! - Time -> [0-9]+
+//! - Timed -> Command @ Time
+//! - Seq -> Timed*
+
+use super::parse_command;
+use crate::format::{format_command, Style};
+use crate::Command;
+
+use nom::character::complete::{char, multispace0, u64 as parse_u64};
+use nom::combinator::{all_consuming, map, verify};
+use nom::multi::many0;
+use nom::sequence::{preceded, separated_pair, terminated};
+use nom::IResult;
+
+/// A command with the time it was done in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedCommand(pub Command, pub u64);
+
+fn parse_timed(i: &str) -> IResult<&str, TimedCommand> {
+    let f = separated_pair(parse_command, char('@'), parse_u64);
+    map(f, |(c, t)| TimedCommand(c, t))(i)
+}
+
+pub fn parse(i: &str) -> IResult<&str, Vec<TimedCommand>> {
+    let p = preceded(multispace0, many0(terminated(parse_timed, multispace0)));
+    let p = verify(p, |v: &Vec<TimedCommand>| {
+        v.windows(2).all(|w| w[0].1 <= w[1].1)
+    });
+    all_consuming(p)(i)
+}
+
+/// Print a timestamped move stream.
+///
+/// The commands are printed in the default style so the output can be read back by `parse`.
+pub fn format(records: &[TimedCommand]) -> String {
+    let style = Style::default();
+    let v: Vec<String> = records
+        .iter()
+        .map(|TimedCommand(c, t)| format!("{}@{}", format_command(*c, &style), t))
+        .collect();
+    v.join(" ")
+}
+
+/// Turns per second between the first and the last move.
+///
+/// Returns `None` if the duration is zero or the last move is before the first.
+pub fn tps(records: &[TimedCommand]) -> Option<f64> {
+    let first = records.first()?.1;
+    let last = records.last()?.1;
+    let duration = last.checked_sub(first).filter(|&d| d > 0)?;
+    let n = (records.len() - 1) as f64;
+    Some(1000. * n / duration as f64)
+}
+
+/// A pause between two consecutive moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pause {
+    /// The index of the move after the pause.
+    pub index: usize,
+    /// The time the pause started.
+    pub start: u64,
+    /// The length of the pause in milliseconds.
+    pub millis: u64,
+}
+
+/// List the pauses that are at least `threshold` milliseconds long.
+///
+/// A move with a timestamp before the previous one is not a pause.
+pub fn pauses(records: &[TimedCommand], threshold: u64) -> Vec<Pause> {
+    let mut v = vec![];
+    for i in 1..records.len() {
+        let start = records[i - 1].1;
+        let millis = match records[i].1.checked_sub(start) {
+            Some(millis) => millis,
+            None => continue,
+        };
+        if millis >= threshold {
+            v.push(Pause {
+                index: i,
+                start,
+                millis,
+            });
+        }
+    }
+    v
+}
+
+/// A run of moves executed without a pause.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    /// The indices of the moves in the window.
+    pub range: std::ops::Range<usize>,
+    pub start: u64,
+    pub end: u64,
+    /// `None` if the window has only one move.
+    pub tps: Option<f64>,
+}
+
+/// Split the moves into execution windows separated by pauses
+/// that are at least `threshold` milliseconds long.
+pub fn windows(records: &[TimedCommand], threshold: u64) -> Vec<Window> {
+    let mut v = vec![];
+    if records.is_empty() {
+        return v;
+    }
+    let mut from = 0;
+    let mut bounds: Vec<usize> = pauses(records, threshold)
+        .into_iter()
+        .map(|p| p.index)
+        .collect();
+    bounds.push(records.len());
+    for to in bounds {
+        let xs = &records[from..to];
+        v.push(Window {
+            range: from..to,
+            start: xs[0].1,
+            end: xs[xs.len() - 1].1,
+            tps: tps(xs),
+        });
+        from = to;
+    }
+    v
+}
+
+#[test]
+fn test_parse() {
+    use crate::Move::*;
+    let v = parse("R@120 U@250 R'@380").unwrap().1;
+    assert_eq!(
+        v,
+        vec![
+            TimedCommand(Command(R, 1), 120),
+            TimedCommand(Command(U, 1), 250),
+            TimedCommand(Command(R, -1), 380),
+        ]
+    );
+    assert_eq!(parse("").unwrap().1, vec![]);
+    assert_eq!(parse(" Rw2'@0\nx@0 ").unwrap().1.len(), 2);
+    assert!(parse("R@").is_err());
+    assert!(parse("R").is_err());
+    assert!(parse("R @1").is_err());
+    assert!(parse("R@200 U@100").is_err());
+}
+#[test]
+fn test_format() {
+    let s = "R@120 U2'@250 r'@380";
+    let v = parse(s).unwrap().1;
+    assert_eq!(format(&v), s);
+    let mut t = 0;
+    let mut records = vec![];
+    for c in crate::random(100) {
+        t += c.1.unsigned_abs() as u64 * 7;
+        records.push(TimedCommand(c, t));
+    }
+    let s = format(&records);
+    assert_eq!(parse(&s).unwrap().1, records);
+    // Every move and count.
+    let records: Vec<TimedCommand> = crate::MOVE_LIST
+        .iter()
+        .flat_map(|&mov| [1, 2, -1, -2].map(|k| TimedCommand(Command(mov, k), 0)))
+        .collect();
+    assert_eq!(parse(&format(&records)).unwrap().1, records);
+}
+#[test]
+fn test_stats() {
+    let v = parse("R@0 U@100 R'@200 U'@300 F@1300 R@1400 U@3000")
+        .unwrap()
+        .1;
+    assert_eq!(tps(&v), Some(2.));
+    assert_eq!(tps(&v[0..1]), None);
+    assert_eq!(tps(&[]), None);
+    assert_eq!(
+        pauses(&v, 500),
+        vec![
+            Pause {
+                index: 4,
+                start: 300,
+                millis: 1000
+            },
+            Pause {
+                index: 6,
+                start: 1400,
+                millis: 1600
+            },
+        ]
+    );
+    let w = windows(&v, 500);
+    assert_eq!(w.len(), 3);
+    assert_eq!(w[0].range, 0..4);
+    assert_eq!((w[0].start, w[0].end), (0, 300));
+    assert_eq!(w[0].tps, Some(10.));
+    assert_eq!(w[1].range, 4..6);
+    assert_eq!(w[2].range, 6..7);
+    assert_eq!(w[2].tps, None);
+    assert!(windows(&[], 500).is_empty());
+}
+#[test]
+fn test_unordered() {
+    use crate::Move::*;
+    let v = vec![
+        TimedCommand(Command(R, 1), 1000),
+        TimedCommand(Command(U, 1), 100),
+        TimedCommand(Command(R, -1), 900),
+    ];
+    assert_eq!(tps(&v), None);
+    assert_eq!(tps(&v[1..]), Some(1.25));
+    assert_eq!(
+        pauses(&v, 500),
+        vec![Pause {
+            index: 2,
+            start: 100,
+            millis: 800
+        }]
+    );
+    assert_eq!(windows(&v, 500).len(), 2);
+}
@@ -0,0 +1,193 @@
+//! Composable goals over stickers.
+//!
+//! A goal is built from sticker sets or piece sets and combined with and/or.
+//! It is the target of `solver::finder` by `Target::Goal`.
+//! The other solvers have fixed goals.
+
+use crate::block::Block;
+use crate::cfop::{B, B2, CROSS_SIDES, D, D_CROSS, F, F2, L, L2, R, R2, U};
+use crate::cubie::{corner_facelets, edge_facelets};
+use crate::matrix::PermutationMatrix;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// The stickers must match the centers of their faces.
+    Solved(Vec<u8>),
+    /// The stickers must match the color of each other.
+    SameColor(Vec<u8>),
+    /// Any state.
+    Ignore,
+    /// All the goals must be met.
+    All(Vec<Goal>),
+    /// One of the goals must be met.
+    Any(Vec<Goal>),
+}
+
+fn color(mat: &PermutationMatrix, facelet: u8) -> u8 {
+    mat.inv_perm[facelet as usize] / 9
+}
+
+impl Goal {
+    /// The corners at the positions must be solved.
+    pub fn corners(corners: &[usize]) -> Goal {
+        Goal::Solved(corners.iter().flat_map(|&i| corner_facelets(i)).collect())
+    }
+    /// The edges at the positions must be solved.
+    pub fn edges(edges: &[usize]) -> Goal {
+        Goal::Solved(edges.iter().flat_map(|&i| edge_facelets(i)).collect())
+    }
+    /// The block must be solved.
+    pub fn block(block: Block) -> Goal {
+        Goal::Solved(block.facelets())
+    }
+    /// The whole cube is solved in any orientation.
+    pub fn solved() -> Goal {
+        Goal::All(
+            [R, L, U, D, F, B]
+                .iter()
+                .map(|face| Goal::SameColor(face.to_vec()))
+                .collect(),
+        )
+    }
+    /// Same as `cfop::cross_solved`.
+    pub fn cross() -> Goal {
+        let mut v = vec![Goal::SameColor(D_CROSS.to_vec())];
+        v.extend(CROSS_SIDES.iter().map(|s| Goal::SameColor(s.to_vec())));
+        Goal::All(v)
+    }
+    /// Same as `cfop::f2l_solved`.
+    pub fn f2l() -> Goal {
+        Goal::All(vec![
+            Goal::SameColor(D.to_vec()),
+            Goal::SameColor(F2.to_vec()),
+            Goal::SameColor(B2.to_vec()),
+            Goal::SameColor(R2.to_vec()),
+            Goal::SameColor(L2.to_vec()),
+        ])
+    }
+    /// Same as `cfop::oll_solved`.
+    pub fn oll() -> Goal {
+        Goal::SameColor(U.to_vec()).and(Goal::f2l())
+    }
+    pub fn and(self, other: Goal) -> Goal {
+        match (self, other) {
+            (Goal::Ignore, x) | (x, Goal::Ignore) => x,
+            (Goal::All(mut v), Goal::All(w)) => {
+                v.extend(w);
+                Goal::All(v)
+            }
+            (Goal::All(mut v), x) => {
+                v.push(x);
+                Goal::All(v)
+            }
+            (x, y) => Goal::All(vec![x, y]),
+        }
+    }
+    pub fn or(self, other: Goal) -> Goal {
+        match (self, other) {
+            (Goal::Ignore, _) | (_, Goal::Ignore) => Goal::Ignore,
+            (Goal::Any(mut v), Goal::Any(w)) => {
+                v.extend(w);
+                Goal::Any(v)
+            }
+            (Goal::Any(mut v), x) => {
+                v.push(x);
+                Goal::Any(v)
+            }
+            (x, y) => Goal::Any(vec![x, y]),
+        }
+    }
+    /// Check if the goal is met.
+    pub fn check(&self, mat: &PermutationMatrix) -> bool {
+        match self {
+            Goal::Solved(v) => v
+                .iter()
+                .all(|&f| color(mat, f) == color(mat, f / 9 * 9 + 4)),
+            Goal::SameColor(v) => v.iter().all(|&f| color(mat, f) == color(mat, v[0])),
+            Goal::Ignore => true,
+            Goal::All(v) => v.iter().all(|g| g.check(mat)),
+            Goal::Any(v) => v.iter().any(|g| g.check(mat)),
+        }
+    }
+    /// The stickers the goal looks at.
+    pub fn mask(&self) -> [bool; 54] {
+        let mut mask = [false; 54];
+        self.fill(&mut mask);
+        mask
+    }
+    fn fill(&self, mask: &mut [bool; 54]) {
+        match self {
+            Goal::Solved(v) | Goal::SameColor(v) => {
+                for &f in v {
+                    mask[f as usize] = true;
+                }
+            }
+            Goal::Ignore => {}
+            Goal::All(v) | Goal::Any(v) => {
+                for g in v {
+                    g.fill(mask);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::{cfop, flatten, matrix};
+
+    fn of(s: &str) -> PermutationMatrix {
+        matrix::of_seq(&flatten(parse(s).unwrap().1))
+    }
+    #[test]
+    fn test_cfop() {
+        let mut states: Vec<PermutationMatrix> = ["", "x y", "RUR'", "RUR'URU2R'", "R", "M2"]
+            .iter()
+            .map(|s| of(s))
+            .collect();
+        states.extend((0..20).map(|_| matrix::of_seq(&crate::random(25))));
+        for m in &states {
+            assert_eq!(Goal::solved().check(m), cfop::solved(m));
+            assert_eq!(Goal::cross().check(m), cfop::cross_solved(m));
+            assert_eq!(Goal::f2l().check(m), cfop::f2l_solved(m));
+            assert_eq!(Goal::oll().check(m), cfop::oll_solved(m));
+        }
+    }
+    #[test]
+    fn test_compose() {
+        // UFR and UF.
+        let g = Goal::corners(&[0]).and(Goal::edges(&[1]));
+        assert!(g.check(&of("L D")));
+        assert!(!g.check(&of("R")));
+        assert!(g.clone().or(Goal::Ignore).check(&of("R")));
+        assert!(g.clone().or(Goal::edges(&[2])).check(&of("R")));
+        assert_eq!(g.mask().iter().filter(|&&b| b).count(), 5);
+        assert_eq!(Goal::Ignore.and(g.clone()), g);
+        // The UF edge moves with the centers by M.
+        let m = of("M");
+        assert!(Goal::edges(&[1]).check(&m));
+        assert!(!Goal::SameColor(vec![18, 23]).check(&m));
+        assert!(Goal::block(Block::B2x2x2(6)).check(&of("x RU")));
+    }
+    #[test]
+    fn test_finder() {
+        use crate::solver::finder::{find, Options, Target};
+        // Solve the F2L and the U face from the Sune case.
+        let goal = Goal::oll();
+        let case = of("RUR'URU2R'");
+        let opts = Options {
+            max_len: 7,
+            ..Options::default()
+        };
+        let v = find(&Target::Goal(case, &goal), &opts);
+        assert!(!v.is_empty());
+        for x in &v {
+            assert!(goal.check(&(matrix::of_seq(&x.sequence()) * case)));
+        }
+        // Same as the predicate.
+        let w = find(&Target::Solve(case, &|m| goal.check(m)), &opts);
+        assert_eq!(v, w);
+    }
+}
@@ -0,0 +1,229 @@
+//! Collection of Roux related tools.
+//!
+//! The first block is on the left and the second block is on the right.
+//! Colors are judged relative to the first block because the M slice moves the centers.
+
+use crate::cfop::with_auf;
+use crate::matrix::{self, PermutationMatrix};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+const FB: [u8; 12] = [12, 13, 14, 15, 16, 17, 27, 30, 33, 39, 42, 51];
+const FB_B: u8 = 52;
+const SB: [u8; 12] = [0, 1, 3, 4, 6, 7, 29, 32, 35, 41, 44, 45];
+const SB_B: u8 = 46;
+const U_CORNERS: [u8; 12] = [2, 8, 9, 11, 18, 20, 24, 26, 36, 38, 47, 53];
+/// UR, UF, UL, UB, DF and DB. The U or D sticker comes first.
+const LSE_EDGES: [[u8; 2]; 6] = [[19, 5], [23, 37], [25, 10], [21, 50], [28, 43], [34, 48]];
+const M_SLICE: [u8; 4] = [37, 50, 43, 48];
+
+fn color(mat: &PermutationMatrix, facelet: u8) -> u8 {
+    mat.inv_perm[facelet as usize] / 9
+}
+/// The colors of the faces in the order of `coord::SURFACE_LIST` given by the first block.
+fn block_colors(mat: &PermutationMatrix) -> [u8; 6] {
+    let (r, l) = (color(mat, 4), color(mat, 13));
+    let (d, f, b) = (color(mat, 30), color(mat, 39), color(mat, 52));
+    let u = (0..6).find(|c| ![r, l, d, f, b].contains(c)).unwrap_or(d);
+    [r, l, u, d, f, b]
+}
+fn all_home(mat: &PermutationMatrix, facelets: &[u8]) -> bool {
+    let colors = block_colors(mat);
+    facelets
+        .iter()
+        .all(|&f| color(mat, f) == colors[f as usize / 9])
+}
+
+/// Check if the first block is solved.
+pub fn first_block_solved(mat: &PermutationMatrix) -> bool {
+    all_home(mat, &FB) && all_home(mat, &[FB_B])
+}
+/// Check if the second block is solved. The colors should match the first block.
+pub fn second_block_solved(mat: &PermutationMatrix) -> bool {
+    all_home(mat, &SB) && all_home(mat, &[SB_B])
+}
+/// Check if both blocks are solved.
+pub fn blocks_solved(mat: &PermutationMatrix) -> bool {
+    first_block_solved(mat) && second_block_solved(mat)
+}
+/// Check if the blocks and the U corners are solved.
+pub fn cmll_solved(mat: &PermutationMatrix) -> bool {
+    blocks_solved(mat) && all_home(mat, &U_CORNERS)
+}
+
+/// Check if the six edges of LSE are oriented.
+///
+/// An edge is good if its U or D sticker faces U or D.
+/// This is reversed for the edges in the M slice while the centers are off by M or M'.
+pub fn lse_eo_solved(mat: &PermutationMatrix) -> bool {
+    let colors = block_colors(mat);
+    let (u, d) = (colors[2], colors[3]);
+    let off = ![u, d].contains(&color(mat, 22));
+    LSE_EDGES.iter().all(|e| {
+        let ud = if [u, d].contains(&color(mat, e[0])) {
+            e[0]
+        } else {
+            e[1]
+        };
+        let faces_ud = ud / 9 == 2 || ud / 9 == 3;
+        let in_m = M_SLICE.contains(&e[1]);
+        faces_ud != (off && in_m)
+    })
+}
+/// Check if the UL and UR edges are solved after the CMLL.
+pub fn ulur_solved(mat: &PermutationMatrix) -> bool {
+    cmll_solved(mat) && all_home(mat, &[19, 5, 25, 10])
+}
+
+/// CMLL algorithms named by the orientation of the corners.
+///
+/// Only R, U, F and L are used so the blocks are kept.
+pub const CMLL_LIST: [(&str, &str); 42] = [
+    ("O1", "FR'FL2F'RFL2F2"),
+    ("O2", "R2U2F2U'R2U2F2UR2U2F2"),
+    ("H1", "RU2R2F2RF2RU2R2F2R"),
+    ("H2", "R'U2FU2FU2F'U2R"),
+    ("H3", "FU2F'U2F'U2F'U2F"),
+    ("H4", "FR'F'RU2F2LFL'F"),
+    ("Pi1", "R'F'U'FU'RUR'UR"),
+    ("Pi2", "R'F2UF2U'F2U'F2R"),
+    ("Pi3", "RU2R2U'R2U'R2U2R"),
+    ("Pi4", "FR'FRF2U2LF'L'F"),
+    ("Pi5", "R2F2RU2LF'L2U2LFR"),
+    ("Pi6", "R2F2RF'L'U2L2FL'U2R"),
+    ("U1", "L2UL'F'LU'L2F'UF"),
+    ("U2", "R2F2LFL'F2R2UFU'F'"),
+    ("U3", "R2F2R'U2RF2R'U2R'"),
+    ("U4", "R'F'U'FUR"),
+    ("U5", "L2UL'F2LU'L'F2L'"),
+    ("U6", "R2U'RF2R'URF2R"),
+    ("T1", "RUR2FRF'ULFL'"),
+    ("T2", "R2F2RU'F'UFR'F2R2"),
+    ("T3", "L'U'LF2U'F'LF'L2UL"),
+    ("T4", "L'U2L'F2LU2L'F2L2"),
+    ("T5", "R'FRU2FU2F'"),
+    ("T6", "LF'L'U2F'U2F"),
+    ("S1", "R'F2RU2LF'L'F"),
+    ("S2", "FR'U2RF'R'FU2F'R"),
+    ("S3", "FR'F'RU2RU2R'"),
+    ("S4", "LU'R'UL'U'R"),
+    ("S5", "R'F2RUL'UL"),
+    ("S6", "RUR'UF'UFU'RU2R'"),
+    ("As1", "RU2R'U2R'FRF'"),
+    ("As2", "F'LU2L'FLF'U2FL'"),
+    ("As3", "R'FRF'LFL'"),
+    ("As4", "RU2R'FR'F'RU'RU'R'"),
+    ("As5", "F'LFR'F2L'F2R"),
+    ("As6", "R'U'RU'R'U2R"),
+    ("L1", "R'F2R'U'RF2R'UR2"),
+    ("L2", "F'U2FU2LFL'"),
+    ("L3", "LF2LUL'F2LU'L2"),
+    ("L4", "FU2F'U2R'F'R"),
+    ("L5", "RU2R2F'LF'RF'L2U2L"),
+    ("L6", "RU2R2FRF'RU2R'"),
+];
+
+/// Which face each sticker of the U corners belongs to by its color.
+fn cmll_signature(mat: &PermutationMatrix) -> u64 {
+    let colors = block_colors(mat);
+    let mut sig = 0;
+    for (i, &f) in U_CORNERS.iter().enumerate() {
+        let k = colors.iter().position(|&c| c == color(mat, f)).unwrap_or(0);
+        sig |= (k as u64) << (3 * i);
+    }
+    sig
+}
+static CMLL_CASES: Lazy<HashMap<u64, (usize, i8, i8)>> = Lazy::new(|| {
+    let mut tbl = HashMap::new();
+    for (i, (_, seq)) in CMLL_LIST.iter().enumerate() {
+        for pre in 0..4 {
+            for post in 0..4 {
+                let m = matrix::of_seq(&with_auf(seq, pre, post)).inv();
+                tbl.entry(cmll_signature(&m)).or_insert((i, pre, post));
+            }
+        }
+    }
+    tbl
+});
+
+/// Recognize the CMLL case.
+///
+/// Returns the name in `CMLL_LIST` and the number of U turns to do before and after the algorithm.
+/// `None` if the blocks aren't solved or only the AUF is left.
+pub fn recognize_cmll(mat: &PermutationMatrix) -> Option<(&'static str, i8, i8)> {
+    if !blocks_solved(mat) {
+        return None;
+    }
+    let (i, pre, post) = CMLL_CASES.get(&cmll_signature(mat))?;
+    Some((CMLL_LIST[*i].0, *pre, *post))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatten;
+    use crate::parser::parse;
+    use crate::{Command, Move};
+
+    fn of(s: &str) -> PermutationMatrix {
+        matrix::of_seq(&flatten(parse(s).unwrap().1))
+    }
+    #[test]
+    fn test_blocks() {
+        let m = PermutationMatrix::identity();
+        assert!(cmll_solved(&m) && ulur_solved(&m) && lse_eo_solved(&m));
+        // The M slice and the U layer don't affect the blocks.
+        let m = of("MU'M2UM'U2");
+        assert!(blocks_solved(&m));
+        let m = of("RUR'");
+        assert!(first_block_solved(&m));
+        assert!(!second_block_solved(&m));
+        let m = of("L'U'L");
+        assert!(!first_block_solved(&m));
+        // The block is judged by its own colors.
+        let m = of("x");
+        assert!(blocks_solved(&m) && cmll_solved(&m));
+        let m = of("r");
+        assert!(first_block_solved(&m));
+        assert!(!second_block_solved(&m));
+    }
+    #[test]
+    fn test_lse_eo() {
+        let m = matrix::of_seq(&[Command(Move::M, 1)]);
+        assert!(lse_eo_solved(&m));
+        assert!(!ulur_solved(&matrix::of_seq(&[Command(Move::U, 1)])));
+        assert!(ulur_solved(&m));
+        assert!(!lse_eo_solved(&of("M'UM")));
+        assert!(!lse_eo_solved(&of("MUM")));
+        assert!(lse_eo_solved(&of("MU2M'U")));
+    }
+    #[test]
+    fn test_cmll_list() {
+        for (name, seq) in CMLL_LIST {
+            let m = matrix::of_seq(&with_auf(seq, 0, 0)).inv();
+            assert!(blocks_solved(&m), "{}", name);
+            assert!(!cmll_solved(&m), "{}", name);
+        }
+    }
+    #[test]
+    fn test_recognize_cmll() {
+        // Every state of the U corners but the U turns.
+        assert_eq!(CMLL_CASES.len(), 24 * 27 - 4);
+        assert_eq!(recognize_cmll(&of("U")), None);
+        assert_eq!(recognize_cmll(&of("L'U'L")), None);
+        for (name, seq) in CMLL_LIST {
+            for pre in 0..4 {
+                for post in 0..4 {
+                    // The M slice and the U edges don't matter.
+                    let mut cs = flatten(parse("M'U2M").unwrap().1);
+                    cs.extend(with_auf(seq, pre, post));
+                    let m = matrix::of_seq(&cs).inv();
+                    let (found, a, b) = recognize_cmll(&m).unwrap();
+                    assert_eq!(found, name);
+                    let (_, seq) = CMLL_LIST.iter().find(|(n, _)| *n == found).unwrap();
+                    assert!(cmll_solved(&(matrix::of_seq(&with_auf(seq, a, b)) * m)));
+                }
+            }
+        }
+    }
+}
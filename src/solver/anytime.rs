@@ -0,0 +1,236 @@
+//! Common interface of the solvers for interactive applications.
+//!
+//! An anytime solver yields progressively shorter solutions as an iterator
+//! so the caller can take the best one so far at any time.
+//! The search stops when the cancellation token is set or the deadline passes.
+
+use crate::matrix::PermutationMatrix;
+use crate::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A flag shared between threads to cancel the search.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Statistics of the search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of visited nodes.
+    pub nodes: u64,
+    /// The deepest search depth reached.
+    pub depth: usize,
+}
+
+/// When to stop the search. The default never stops.
+#[derive(Clone, Debug, Default)]
+pub struct Control {
+    token: CancelToken,
+    deadline: Option<Instant>,
+    stats: Stats,
+    stopped: bool,
+}
+impl Control {
+    pub fn new(token: CancelToken, deadline: Option<Instant>) -> Self {
+        Self {
+            token,
+            deadline,
+            ..Self::default()
+        }
+    }
+    /// Count a node and check if the search should stop.
+    /// The clock is checked only every 1024 nodes.
+    pub(crate) fn tick(&mut self) -> bool {
+        self.stats.nodes += 1;
+        if self.stats.nodes.is_multiple_of(1024) {
+            self.check();
+        }
+        self.stopped
+    }
+    /// Check if the search should stop now.
+    pub(crate) fn check(&mut self) -> bool {
+        if self.token.is_cancelled() || self.deadline.is_some_and(|t| Instant::now() >= t) {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+    pub(crate) fn reach(&mut self, depth: usize) {
+        self.stats.depth = self.stats.depth.max(depth);
+    }
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+/// A running search that yields solutions one by one.
+pub trait Stream {
+    /// The next solution shorter than the previous ones.
+    /// `None` if no more solution is found in the time.
+    fn next_solution(&mut self) -> Option<Vec<Command>>;
+    fn stats(&self) -> Stats;
+}
+
+/// The solutions of an anytime solver in the order they are found.
+pub struct Solutions<'a>(Box<dyn Stream + 'a>);
+impl<'a> Solutions<'a> {
+    pub fn new(stream: impl Stream + 'a) -> Self {
+        Self(Box::new(stream))
+    }
+    pub fn stats(&self) -> Stats {
+        self.0.stats()
+    }
+}
+impl Iterator for Solutions<'_> {
+    type Item = Vec<Command>;
+    fn next(&mut self) -> Option<Vec<Command>> {
+        self.0.next_solution()
+    }
+}
+
+pub trait AnytimeSolver {
+    /// Start solving the state. The search runs while the iterator is advanced.
+    fn start<'a>(&'a self, m: &PermutationMatrix, ctl: Control) -> Solutions<'a>;
+}
+
+/// A solver that returns one solution at once like `thistlethwaite`.
+///
+/// The solver can't be stopped while running but it doesn't start after the cancellation.
+pub struct Once<F>(pub F);
+
+struct OnceStream<'a, F> {
+    f: &'a F,
+    m: PermutationMatrix,
+    ctl: Control,
+    done: bool,
+}
+impl<F: Fn(&PermutationMatrix) -> Option<Vec<Command>>> Stream for OnceStream<'_, F> {
+    fn next_solution(&mut self) -> Option<Vec<Command>> {
+        if self.done || self.ctl.check() {
+            return None;
+        }
+        self.done = true;
+        let sol = (self.f)(&self.m)?;
+        self.ctl.reach(sol.len());
+        Some(sol)
+    }
+    fn stats(&self) -> Stats {
+        self.ctl.stats()
+    }
+}
+impl<F: Fn(&PermutationMatrix) -> Option<Vec<Command>>> AnytimeSolver for Once<F> {
+    fn start<'a>(&'a self, m: &PermutationMatrix, ctl: Control) -> Solutions<'a> {
+        Solutions::new(OnceStream {
+            f: &self.0,
+            m: *m,
+            ctl,
+            done: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::solved;
+    use crate::flatten;
+    use crate::matrix;
+    use crate::parser::parse;
+    use crate::solver::{kociemba, optimal, thistlethwaite};
+    use once_cell::sync::Lazy;
+    use std::time::Duration;
+
+    static SMALL: Lazy<optimal::Solver> = Lazy::new(optimal::Solver::small);
+
+    fn check(solver: &dyn AnytimeSolver, m: &PermutationMatrix, ctl: Control) -> Vec<usize> {
+        let mut sols = solver.start(m, ctl);
+        let mut lens = vec![];
+        for sol in &mut sols {
+            assert!(solved(&(matrix::of_seq(&sol) * *m)));
+            lens.push(sol.len());
+        }
+        assert!(lens.windows(2).all(|w| w[0] > w[1]));
+        if !lens.is_empty() {
+            assert!(sols.stats().depth > 0);
+        }
+        lens
+    }
+    #[test]
+    fn test_solvers() {
+        let opts = kociemba::Options {
+            max_length: 24,
+            ..kociemba::Options::default()
+        };
+        let phases = Once(|m: &PermutationMatrix| {
+            let v = thistlethwaite::solve(m)?;
+            Some(v.into_iter().flat_map(|p| p.moves).collect())
+        });
+        let solvers: Vec<&dyn AnytimeSolver> = vec![&opts, &phases];
+        for _ in 0..3 {
+            let m = matrix::of_seq(&crate::random(30));
+            for &solver in &solvers {
+                let deadline = Instant::now() + Duration::from_secs(30);
+                let lens = check(solver, &m, Control::new(CancelToken::new(), Some(deadline)));
+                assert!(!lens.is_empty());
+            }
+        }
+        // The optimal solver yields only one solution.
+        let m = matrix::of_seq(&flatten(parse("RUR'U'").unwrap().1));
+        assert_eq!(check(&*SMALL, &m, Control::default()), vec![4]);
+        let opts = kociemba::Options {
+            max_length: 0,
+            ..opts
+        };
+        assert_eq!(check(&opts, &m, Control::default()).pop(), Some(4));
+    }
+    #[test]
+    fn test_cancel() {
+        let m = matrix::of_seq(&crate::random(30));
+        let token = CancelToken::new();
+        token.cancel();
+        let opts = kociemba::Options::default();
+        let solvers: Vec<&dyn AnytimeSolver> = vec![&opts, &*SMALL];
+        for &solver in &solvers {
+            let mut sols = solver.start(&m, Control::new(token.clone(), None));
+            assert_eq!(sols.next(), None);
+            assert!(sols.stats().nodes <= 1024);
+        }
+        // Cancelled from another thread while searching for the impossible.
+        let opts = kociemba::Options {
+            max_length: 0,
+            ..kociemba::Options::default()
+        };
+        let token = CancelToken::new();
+        let t = token.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(500));
+            t.cancel();
+        });
+        let start = Instant::now();
+        let mut sols = opts.start(&m, Control::new(token, None));
+        while sols.next().is_some() {}
+        assert!(start.elapsed() < Duration::from_secs(20));
+        assert!(sols.stats().nodes > 0);
+        handle.join().unwrap();
+    }
+    #[test]
+    fn test_deadline() {
+        let m = matrix::of_seq(&crate::random(30));
+        let ctl = Control::new(CancelToken::new(), Some(Instant::now()));
+        assert!(check(&*SMALL, &m, ctl).is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! Beginner's layer by layer solver.
+//!
+//! The cross is solved on D through the daisy and the last layer on U.
+//! Each step uses the algorithms a beginner learns with the U turns and the y rotations to set up.
+//! Every step comes with an explanation and the state after it so it can be shown step by step.
+
+use super::{move_count, turn, Step};
+use crate::cfop;
+use crate::cubie::{self, corner_facelets, edge_facelets};
+use crate::matrix::{self, PermutationMatrix};
+use crate::{flatten, parser, Command, Move};
+
+/// A step of the solution with its explanation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplainedStep {
+    pub step: Step,
+    pub explanation: &'static str,
+    /// The state after the step.
+    pub state: PermutationMatrix,
+}
+
+const DAISY: &str = "Hold the cube with the D center at the bottom. \
+Bring the four edges of the D color around the U center with the D color facing up to make a daisy. \
+The edges already solved on D can be left there.";
+const CROSS: &str = "Turn U until a petal of the daisy is above the center of its side color \
+and turn that face twice to bring it down. Repeat for the four petals.";
+const CORNERS: &str = "Put a corner of the D color above its place at the front right \
+and repeat R U R' U' until it is solved. \
+A corner stuck in a wrong place of the bottom layer is taken out with the same moves.";
+const EDGES: &str = "Match an edge of the top layer with its side center at the front. \
+Insert it to the right with U R U' R' U' F' U F or to the left with U' L' U L U F U' F'. \
+An edge stuck in a wrong place is taken out by inserting another edge there.";
+const LL_CROSS: &str = "Do F R U R' U' F' to make a cross of the U color on top. \
+Hold a line horizontally or an L at the back left before doing it.";
+const LL_EDGES: &str = "Do R U R' U R U2 R' U to swap the edges at the front and the left \
+until the side colors of all the edges match their centers after turning U.";
+const CORNER_PERMUTATION: &str = "Hold a corner at the right place at the front right \
+and do U R U' L' U R' U' L until all the corners are at the right places.";
+const CORNER_ORIENTATION: &str = "Hold a corner to twist at the front right \
+and repeat R' D' R D until its U color faces up. Turn U to bring the next corner there \
+and repeat. The bottom layers come back after all the corners are twisted.";
+
+fn parse(s: &str) -> Vec<Command> {
+    flatten(parser::parse(s).unwrap().1)
+}
+fn color(m: &PermutationMatrix, facelet: u8) -> u8 {
+    m.inv_perm[facelet as usize] / 9
+}
+fn is_home(m: &PermutationMatrix, facelet: u8) -> bool {
+    color(m, facelet) == color(m, facelet / 9 * 9 + 4)
+}
+
+/// The number of the edges around U with the D color facing up and the solved edges of the cross.
+fn petals(m: &PermutationMatrix) -> usize {
+    let d = color(m, 31);
+    let n = (0..4)
+        .filter(|&i| color(m, edge_facelets(i)[0]) == d)
+        .count();
+    n + edges_home(m, 4..8)
+}
+fn edges_home(m: &PermutationMatrix, edges: std::ops::Range<usize>) -> usize {
+    edges
+        .filter(|&i| edge_facelets(i).iter().all(|&f| is_home(m, f)))
+        .count()
+}
+fn corners_home(m: &PermutationMatrix, corners: std::ops::Range<usize>) -> usize {
+    corners
+        .filter(|&i| corner_facelets(i).iter().all(|&f| is_home(m, f)))
+        .count()
+}
+fn first_layer_solved(m: &PermutationMatrix) -> bool {
+    edges_home(m, 4..8) == 4 && corners_home(m, 4..8) == 4
+}
+fn ll_cross_solved(m: &PermutationMatrix) -> bool {
+    cfop::f2l_solved(m) && (0..4).all(|i| is_home(m, edge_facelets(i)[0]))
+}
+/// The edges of the U layer are solved up to the AUF.
+fn ll_edges_solved(m: &PermutationMatrix) -> bool {
+    (0..4).any(|k| {
+        let m = matrix::of_seq(&[Command(Move::U, k)]) * *m;
+        ll_cross_solved(&m) && edges_home(&m, 0..4) == 4
+    })
+}
+/// The corners of the U layer are at the right places ignoring the orientation.
+fn corners_permuted(m: &PermutationMatrix) -> bool {
+    (0..4).all(|i| {
+        let fs = corner_facelets(i);
+        let mut a = fs.map(|f| color(m, f));
+        let mut b = fs.map(|f| color(m, f / 9 * 9 + 4));
+        a.sort_unstable();
+        b.sort_unstable();
+        a == b
+    })
+}
+
+/// Sequences of `y^a U^b alg^n`.
+fn macros(rotate: bool, auf: bool, algs: &[&str], reps: usize) -> Vec<Vec<Command>> {
+    let mut v = vec![];
+    for a in 0..if rotate { 4 } else { 1 } {
+        for b in 0..if auf { 4 } else { 1 } {
+            for alg in algs {
+                let alg = parse(alg);
+                for n in 1..=reps {
+                    let mut cs: Vec<Command> = turn(Move::y, a).into_iter().collect();
+                    cs.extend(turn(Move::U, b));
+                    for _ in 0..n {
+                        cs.extend(&alg);
+                    }
+                    v.push(cs);
+                }
+            }
+        }
+    }
+    v.sort_by_key(|cs| move_count(cs));
+    v
+}
+
+fn dfs(
+    m: &PermutationMatrix,
+    mats: &[PermutationMatrix],
+    togo: usize,
+    goal: &impl Fn(&PermutationMatrix) -> bool,
+    path: &mut Vec<usize>,
+) -> bool {
+    if goal(m) {
+        return true;
+    }
+    if togo == 0 {
+        return false;
+    }
+    for (i, x) in mats.iter().enumerate() {
+        path.push(i);
+        if dfs(&(*x * *m), mats, togo - 1, goal, path) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+/// The fewest sequences of the macros to reach the goal.
+/// `None` if more than `max_depth` are needed.
+fn search(
+    m: &PermutationMatrix,
+    macros: &[Vec<Command>],
+    max_depth: usize,
+    goal: impl Fn(&PermutationMatrix) -> bool,
+) -> Option<Vec<Command>> {
+    let mats: Vec<PermutationMatrix> = macros.iter().map(|cs| matrix::of_seq(cs)).collect();
+    let mut path = vec![];
+    (0..=max_depth).find(|&d| dfs(m, &mats, d, &goal, &mut path))?;
+    Some(path.iter().flat_map(|&i| macros[i].clone()).collect())
+}
+/// Repeat the search until `count` reaches 4.
+fn piece_by_piece(
+    m: &PermutationMatrix,
+    macros: &[Vec<Command>],
+    max_depth: usize,
+    keep: impl Fn(&PermutationMatrix) -> bool,
+    count: impl Fn(&PermutationMatrix) -> usize,
+) -> Option<Vec<Command>> {
+    let mut m = *m;
+    let mut moves = vec![];
+    while count(&m) < 4 {
+        let n = count(&m);
+        let cs = search(&m, macros, max_depth, |x| keep(x) && count(x) > n)?;
+        m = matrix::of_seq(&cs) * m;
+        moves.extend(cs);
+    }
+    Some(moves)
+}
+
+/// Twist the corners of the U layer at UFR with R' D' R D and solve the cube by U.
+fn orient_corners(m: &PermutationMatrix) -> Vec<Command> {
+    let alg = parse("R'D'RD");
+    let u = corner_facelets(0)[0];
+    let mut m = *m;
+    let mut moves = vec![];
+    let mut apply = |m: &mut PermutationMatrix, cs: &[Command]| {
+        *m = matrix::of_seq(cs) * *m;
+        moves.extend(cs);
+    };
+    loop {
+        while color(&m, u) != color(&m, 22) {
+            apply(&mut m, &alg);
+        }
+        if (0..4).all(|i| is_home(&m, corner_facelets(i)[0])) {
+            break;
+        }
+        apply(&mut m, &[Command(Move::U, 1)]);
+    }
+    let k = (0..4)
+        .find(|&k| cfop::solved(&(matrix::of_seq(&[Command(Move::U, k)]) * m)))
+        .unwrap();
+    moves.extend(turn(Move::U, k));
+    moves
+}
+
+/// Solve the cube by the beginner's method.
+///
+/// The steps are Daisy, Cross, First layer corners, Second layer edges, Last layer cross,
+/// Last layer edges, Corner permutation and Corner orientation. The moves are empty if
+/// the step is skipped. `None` if the state is broken.
+pub fn solve(m: &PermutationMatrix) -> Option<Vec<ExplainedStep>> {
+    let o = cubie::Oriented::new(m)?;
+    if !o.cube.is_solvable() {
+        return None;
+    }
+    let mut x = *m;
+    let mut steps = vec![];
+    let mut push = |x: &mut PermutationMatrix, name: &str, explanation, moves: Vec<Command>| {
+        *x = matrix::of_seq(&moves) * *x;
+        steps.push(ExplainedStep {
+            step: Step {
+                name: name.to_owned(),
+                moves,
+            },
+            explanation,
+            state: *x,
+        });
+    };
+
+    // Bring the centers home first.
+    let mut moves = cubie::rotations()[o.rotation].clone();
+    let y = matrix::of_seq(&moves) * x;
+    let faces: Vec<String> = (0..18).map(|i| cubie::command_of(i).to_string()).collect();
+    let faces: Vec<&str> = faces.iter().map(|s| s.as_str()).collect();
+    let daisy = macros(false, false, &faces, 1);
+    moves.extend(piece_by_piece(&y, &daisy, 5, |_| true, petals)?);
+    push(&mut x, "Daisy", DAISY, moves);
+
+    let cross = macros(false, true, &["F2", "R2", "B2", "L2"], 1);
+    let moves = piece_by_piece(&x, &cross, 1, |_| true, |x| edges_home(x, 4..8))?;
+    push(&mut x, "Cross", CROSS, moves);
+
+    let corners = macros(true, true, &["RUR'U'"], 5);
+    let moves = piece_by_piece(
+        &x,
+        &corners,
+        2,
+        |x| edges_home(x, 4..8) == 4,
+        |x| corners_home(x, 4..8),
+    )?;
+    push(&mut x, "First layer corners", CORNERS, moves);
+
+    let edges = macros(true, true, &["URU'R'U'F'UF", "U'L'ULUFU'F'"], 1);
+    let moves = piece_by_piece(&x, &edges, 2, first_layer_solved, |x| edges_home(x, 8..12))?;
+    push(&mut x, "Second layer edges", EDGES, moves);
+
+    let ll_cross = macros(false, true, &["FRUR'U'F'"], 1);
+    let moves = search(&x, &ll_cross, 3, ll_cross_solved)?;
+    push(&mut x, "Last layer cross", LL_CROSS, moves);
+
+    let ll_edges = macros(false, true, &["RUR'URU2R'U"], 1);
+    let mut moves = search(&x, &ll_edges, 2, ll_edges_solved)?;
+    let k = (0..4)
+        .find(|&k| {
+            let y = matrix::of_seq(&moves) * x;
+            edges_home(&(matrix::of_seq(&[Command(Move::U, k)]) * y), 0..4) == 4
+        })
+        .unwrap();
+    moves.extend(turn(Move::U, k));
+    push(&mut x, "Last layer edges", LL_EDGES, moves);
+
+    let permutation = macros(true, false, &["URU'L'UR'U'L"], 2);
+    let moves = search(&x, &permutation, 2, |x| {
+        ll_cross_solved(x) && edges_home(x, 0..4) == 4 && corners_permuted(x)
+    })?;
+    push(&mut x, "Corner permutation", CORNER_PERMUTATION, moves);
+
+    let moves = orient_corners(&x);
+    push(&mut x, "Corner orientation", CORNER_ORIENTATION, moves);
+    Some(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        let goals: [fn(&PermutationMatrix) -> bool; 8] = [
+            |x| petals(x) == 4,
+            |x| edges_home(x, 4..8) == 4,
+            first_layer_solved,
+            cfop::f2l_solved,
+            ll_cross_solved,
+            |x| ll_cross_solved(x) && edges_home(x, 0..4) == 4,
+            corners_permuted,
+            cfop::solved,
+        ];
+        for _ in 0..20 {
+            let m = matrix::of_seq(&crate::random(25));
+            let steps = solve(&m).unwrap();
+            assert_eq!(steps.len(), 8);
+            let mut x = m;
+            for (s, goal) in steps.iter().zip(goals) {
+                x = matrix::of_seq(&s.step.moves) * x;
+                assert_eq!(x, s.state);
+                assert!(goal(&x), "{}", s.step);
+            }
+            assert!(cfop::solved(&steps[7].state));
+        }
+    }
+    #[test]
+    fn test_broken() {
+        let mut x = cubie::CubieCube::identity();
+        x.co[0] = 1;
+        assert!(solve(&x.to_matrix()).is_none());
+    }
+    #[test]
+    fn test_solved() {
+        let steps = solve(&PermutationMatrix::identity()).unwrap();
+        assert!(steps.iter().all(|s| s.step.moves.is_empty()));
+        // The rotation to bring the centers home is in the first step.
+        let m = matrix::of_seq(&parse("x y"));
+        let steps = solve(&m).unwrap();
+        assert_eq!(steps[0].step.move_count(), 0);
+        assert!(steps[1..].iter().all(|s| s.step.moves.is_empty()));
+    }
+}
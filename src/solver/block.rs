@@ -0,0 +1,422 @@
+//! IDA* over the positions of a few pieces.
+//!
+//! A piece is tracked by the position of its reference sticker,
+//! so any move including slices and wide turns can be used.
+//!
+//! `solve` searches the blocks of `block::Block` with face turns.
+//! One search for each kind is shared by all the locations through the whole cube rotations.
+
+use crate::block::{Block, BlockKind};
+use crate::coord::Surface;
+use crate::cubie::{self, corner_facelets, edge_facelets};
+use crate::matrix::{self, PermutationMatrix};
+use crate::{axis, simplify, Command, Move};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+
+/// The number of stickers of corners or edges.
+const N_STICKER: usize = 24;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Piece {
+    Corner(usize),
+    Edge(usize),
+}
+
+/// Stickers of corners or edges. The sticker `o` of the piece at `i` is numbered `n * i + o`.
+struct Stickers {
+    facelets: Vec<u8>,
+    id: [u8; 54],
+}
+impl Stickers {
+    fn new(facelets: Vec<u8>) -> Self {
+        let mut id = [u8::MAX; 54];
+        for (k, &f) in facelets.iter().enumerate() {
+            id[f as usize] = k as u8;
+        }
+        Self { facelets, id }
+    }
+}
+static CORNERS: Lazy<Stickers> =
+    Lazy::new(|| Stickers::new((0..8).flat_map(corner_facelets).collect()));
+static EDGES: Lazy<Stickers> =
+    Lazy::new(|| Stickers::new((0..12).flat_map(edge_facelets).collect()));
+
+fn stickers(p: Piece) -> (&'static Stickers, usize) {
+    match p {
+        Piece::Corner(i) => (&CORNERS, 3 * i),
+        Piece::Edge(i) => (&EDGES, 2 * i),
+    }
+}
+
+/// Where the sticker goes by the move. Indexed by the kind of the piece.
+type Transition = [[u8; N_STICKER]; 2];
+
+fn transition(c: Command) -> Transition {
+    let mv = matrix::of_seq(&[c]);
+    let mut dest = [0; 54];
+    for (q, &p) in mv.inv_perm.iter().enumerate() {
+        dest[p as usize] = q as u8;
+    }
+    let mut t = [[0; N_STICKER]; 2];
+    for (k, s) in [&*CORNERS, &*EDGES].iter().enumerate() {
+        for (i, &f) in s.facelets.iter().enumerate() {
+            t[k][i] = s.id[dest[f as usize] as usize];
+        }
+    }
+    t
+}
+
+/// Searches a set of pieces with a set of moves.
+///
+/// The subsets of the pieces have exact distance tables and the heuristic is the maximum of them.
+pub(crate) struct BlockSearch {
+    pieces: Vec<Piece>,
+    moves: Vec<Command>,
+    trans: Vec<Transition>,
+    /// Exact distances of the subsets of the pieces.
+    tables: Vec<(Vec<usize>, Vec<u8>)>,
+}
+impl BlockSearch {
+    pub fn new(pieces: Vec<Piece>, faces: &[Move], subsets: Vec<Vec<usize>>) -> Self {
+        let moves: Vec<Command> = faces
+            .iter()
+            .flat_map(|&face| [1, 2, -1].map(|k| Command(face, k)))
+            .collect();
+        let trans = moves.iter().map(|&c| transition(c)).collect();
+        let mut x = Self {
+            pieces,
+            moves,
+            trans,
+            tables: vec![],
+        };
+        x.tables = subsets
+            .into_iter()
+            .map(|sub| {
+                let tbl = x.table(&sub);
+                (sub, tbl)
+            })
+            .collect();
+        x
+    }
+    fn kind(&self, k: usize) -> usize {
+        match self.pieces[k] {
+            Piece::Corner(_) => 0,
+            Piece::Edge(_) => 1,
+        }
+    }
+    fn solved(&self) -> Vec<u8> {
+        self.pieces.iter().map(|&p| stickers(p).1 as u8).collect()
+    }
+    fn apply(&self, ids: &[u8], m: usize) -> Vec<u8> {
+        let t = &self.trans[m];
+        ids.iter()
+            .enumerate()
+            .map(|(k, &i)| t[self.kind(k)][i as usize])
+            .collect()
+    }
+    fn index(sub: &[usize], ids: &[u8]) -> usize {
+        sub.iter()
+            .rev()
+            .fold(0, |acc, &k| acc * N_STICKER + ids[k] as usize)
+    }
+    /// BFS over the positions of the subset of the pieces.
+    fn table(&self, sub: &[usize]) -> Vec<u8> {
+        let mut dist = vec![u8::MAX; N_STICKER.pow(sub.len() as u32)];
+        let start = self.solved();
+        dist[Self::index(sub, &start)] = 0;
+        let mut q = VecDeque::new();
+        q.push_back(start);
+        while let Some(x) = q.pop_front() {
+            let d = dist[Self::index(sub, &x)] + 1;
+            for m in 0..self.moves.len() {
+                let y = self.apply(&x, m);
+                let i = Self::index(sub, &y);
+                if dist[i] == u8::MAX {
+                    dist[i] = d;
+                    q.push_back(y);
+                }
+            }
+        }
+        dist
+    }
+    fn heuristic(&self, ids: &[u8]) -> usize {
+        self.tables
+            .iter()
+            .map(|(sub, tbl)| tbl[Self::index(sub, ids)] as usize)
+            .max()
+            .unwrap_or(0)
+    }
+    /// The positions of the pieces in the state.
+    fn ids(&self, m: &PermutationMatrix) -> Vec<u8> {
+        let mut pos = [0; 54];
+        for (q, &p) in m.inv_perm.iter().enumerate() {
+            pos[p as usize] = q;
+        }
+        self.pieces
+            .iter()
+            .map(|&p| {
+                let (s, i) = stickers(p);
+                s.id[pos[s.facelets[i] as usize]]
+            })
+            .collect()
+    }
+    fn follows(&self, prev: Option<usize>, m: usize) -> bool {
+        match prev {
+            None => true,
+            // Moves on the same axis commute so they are kept in the order of `moves`.
+            Some(p) => {
+                let (prev, cur) = (self.moves[p].0, self.moves[m].0);
+                prev != cur && !(axis(prev) == axis(cur) && p > m)
+            }
+        }
+    }
+    fn dfs(&self, ids: &[u8], togo: usize, path: &mut Vec<usize>) -> bool {
+        let h = self.heuristic(ids);
+        if h == 0 {
+            return true;
+        }
+        if h > togo {
+            return false;
+        }
+        for m in 0..self.moves.len() {
+            if !self.follows(path.last().copied(), m) {
+                continue;
+            }
+            path.push(m);
+            if self.dfs(&self.apply(ids, m), togo - 1, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+    fn dfs_all(&self, ids: &[u8], togo: usize, path: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+        let h = self.heuristic(ids);
+        if h > togo {
+            return;
+        }
+        if togo == 0 {
+            out.push(path.clone());
+            return;
+        }
+        for m in 0..self.moves.len() {
+            if !self.follows(path.last().copied(), m) {
+                continue;
+            }
+            path.push(m);
+            self.dfs_all(&self.apply(ids, m), togo - 1, path, out);
+            path.pop();
+        }
+    }
+    /// All the shortest sequences to solve the pieces. Empty if they're longer than `max_depth`.
+    pub fn solve_all(&self, m: &PermutationMatrix, max_depth: usize) -> Vec<Vec<Command>> {
+        let ids = self.ids(m);
+        for depth in 0..=max_depth {
+            let mut out = vec![];
+            self.dfs_all(&ids, depth, &mut vec![], &mut out);
+            if !out.is_empty() {
+                return out
+                    .into_iter()
+                    .map(|p| p.into_iter().map(|k| self.moves[k]).collect())
+                    .collect();
+            }
+        }
+        vec![]
+    }
+    /// The shortest sequence to solve the pieces. `None` if it's longer than `max_depth`.
+    pub fn solve(&self, m: &PermutationMatrix, max_depth: usize) -> Option<Vec<Command>> {
+        let ids = self.ids(m);
+        let mut path = vec![];
+        for depth in 0..=max_depth {
+            if self.dfs(&ids, depth, &mut path) {
+                return Some(path.into_iter().map(|k| self.moves[k]).collect());
+            }
+        }
+        None
+    }
+}
+/// The pieces of the block: two corners and three edges.
+/// The heuristic is the maximum over the edges with either corner.
+pub(crate) fn block(corners: [usize; 2], edges: [usize; 3], faces: &[Move]) -> BlockSearch {
+    let mut pieces: Vec<Piece> = edges.iter().map(|&i| Piece::Edge(i)).collect();
+    pieces.extend(corners.iter().map(|&i| Piece::Corner(i)));
+    BlockSearch::new(pieces, faces, vec![vec![0, 1, 2, 3], vec![0, 1, 2, 4]])
+}
+
+/// The location searched directly and the subsets of its pieces for the heuristic.
+/// The pieces are the edges and then the corners of the block.
+fn canonical(kind: BlockKind) -> (Block, Vec<Vec<usize>>) {
+    match kind {
+        // DBL with DL, DB and BL.
+        BlockKind::B2x2x2 => (Block::B2x2x2(6), vec![vec![0, 1, 2, 3]]),
+        // The two 2x2x2 blocks.
+        BlockKind::B2x2x3 => (Block::B2x2x3(6), vec![vec![0, 1, 3, 5], vec![1, 2, 4, 6]]),
+        // The cross and the three pairs with the edges of the cross.
+        BlockKind::F2lMinus1 => (
+            Block::F2lMinus1(Surface::D, 4),
+            vec![
+                vec![0, 1, 2, 3],
+                vec![1, 2, 4, 7],
+                vec![2, 3, 5, 8],
+                vec![3, 0, 6, 9],
+            ],
+        ),
+    }
+}
+struct KindSearch {
+    search: BlockSearch,
+    /// The locations with the sorted stickers.
+    locations: Vec<(Block, Vec<u8>)>,
+    /// The rotation that brings each location to the canonical location.
+    rotation: HashMap<Block, usize>,
+}
+impl KindSearch {
+    fn new(kind: BlockKind) -> Self {
+        let (b, subsets) = canonical(kind);
+        let mut pieces: Vec<Piece> = b.edges().into_iter().map(Piece::Edge).collect();
+        pieces.extend(b.corners().into_iter().map(Piece::Corner));
+        let faces = [Move::R, Move::L, Move::U, Move::D, Move::F, Move::B];
+        let mut x = Self {
+            search: BlockSearch::new(pieces, &faces, subsets),
+            locations: Block::all(kind)
+                .into_iter()
+                .map(|b| (b, sorted(b.facelets())))
+                .collect(),
+            rotation: HashMap::new(),
+        };
+        for (k, rot) in cubie::rotations().iter().enumerate() {
+            let image = x.image(&matrix::of_seq(rot), b);
+            x.rotation.entry(image).or_insert(k);
+        }
+        x
+    }
+    /// The location in the state `m` of the location `b` in the state `r * m`.
+    fn image(&self, r: &PermutationMatrix, b: Block) -> Block {
+        let fs = sorted(
+            b.facelets()
+                .iter()
+                .map(|&f| r.inv_perm[f as usize])
+                .collect(),
+        );
+        self.locations.iter().find(|x| x.1 == fs).unwrap().0
+    }
+    /// The shortest solutions of the location for the state with the centers home.
+    fn solve(&self, x: &PermutationMatrix, b: Block, max_depth: usize) -> Vec<Vec<Command>> {
+        let k = self.rotation[&b];
+        let r = matrix::of_seq(&cubie::rotations()[k]);
+        let y = r * *x * r.inv();
+        self.search
+            .solve_all(&y, max_depth)
+            .into_iter()
+            .map(|cs| cs.into_iter().map(|c| cubie::relabel(c, k)).collect())
+            .collect()
+    }
+}
+fn sorted(mut v: Vec<u8>) -> Vec<u8> {
+    v.sort_unstable();
+    v
+}
+static SEARCH_2X2X2: Lazy<KindSearch> = Lazy::new(|| KindSearch::new(BlockKind::B2x2x2));
+static SEARCH_2X2X3: Lazy<KindSearch> = Lazy::new(|| KindSearch::new(BlockKind::B2x2x3));
+static SEARCH_F2L_MINUS_1: Lazy<KindSearch> = Lazy::new(|| KindSearch::new(BlockKind::F2lMinus1));
+
+/// The shortest solutions of a block location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockSolutions {
+    pub block: Block,
+    pub solutions: Vec<Vec<Command>>,
+}
+
+/// The shortest solutions for every location of the kind with face turns.
+///
+/// The solutions are cancelled by `simplify` and the duplicates are removed.
+/// The locations are sorted by the length and the ones longer than `max_depth` are left out.
+/// Empty if the state is broken.
+pub fn solve(m: &PermutationMatrix, kind: BlockKind, max_depth: usize) -> Vec<BlockSolutions> {
+    let o = match cubie::Oriented::new(m) {
+        Some(o) => o,
+        None => return vec![],
+    };
+    let x = o.cube.to_matrix();
+    let r = matrix::of_seq(&cubie::rotations()[o.rotation]);
+    let search: &KindSearch = match kind {
+        BlockKind::B2x2x2 => &SEARCH_2X2X2,
+        BlockKind::B2x2x3 => &SEARCH_2X2X3,
+        BlockKind::F2lMinus1 => &SEARCH_F2L_MINUS_1,
+    };
+    let mut v: Vec<BlockSolutions> = Block::all(kind)
+        .into_iter()
+        .filter_map(|b| {
+            let mut solutions: Vec<Vec<Command>> = vec![];
+            for cs in search.solve(&x, b, max_depth) {
+                let cs = simplify(&o.relabel(&cs));
+                if !solutions.contains(&cs) {
+                    solutions.push(cs);
+                }
+            }
+            let n = solutions.iter().map(|cs| cs.len()).min()?;
+            solutions.retain(|cs| cs.len() == n);
+            Some(BlockSolutions {
+                block: search.image(&r, b),
+                solutions,
+            })
+        })
+        .collect();
+    v.sort_by_key(|x| x.solutions[0].len());
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(m: &PermutationMatrix, kind: BlockKind, max_depth: usize) -> Vec<BlockSolutions> {
+        let v = solve(m, kind, max_depth);
+        for (i, x) in v.iter().enumerate() {
+            assert_eq!(x.block.kind(), kind);
+            let n = x.solutions[0].len();
+            assert!(n <= max_depth);
+            if i > 0 {
+                assert!(v[i - 1].solutions[0].len() <= n);
+            }
+            for (j, cs) in x.solutions.iter().enumerate() {
+                assert_eq!(cs.len(), n);
+                assert_eq!(simplify(cs), *cs);
+                assert!(!x.solutions[..j].contains(cs));
+                assert!(x.block.solved(&(matrix::of_seq(cs) * *m)), "{:?}", x.block);
+            }
+        }
+        v
+    }
+    #[test]
+    fn test_solve() {
+        for _ in 0..5 {
+            let m = matrix::of_seq(&crate::random(25));
+            let v = check(&m, BlockKind::B2x2x2, 8);
+            assert_eq!(v.len(), 8);
+        }
+        // Every location is within the scramble of face turns.
+        for _ in 0..5 {
+            let cs: Vec<Command> = (0..4)
+                .map(|_| cubie::command_of(rand::random::<usize>() % cubie::N_MOVE))
+                .collect();
+            let m = matrix::of_seq(&cs);
+            assert_eq!(check(&m, BlockKind::B2x2x3, 4).len(), 12);
+            assert_eq!(check(&m, BlockKind::F2lMinus1, 4).len(), 24);
+        }
+        // The rotations and the slices are fine.
+        let m = matrix::of_seq(&crate::flatten(crate::parser::parse("x M").unwrap().1));
+        let v = check(&m, BlockKind::B2x2x3, 4);
+        assert_eq!(v.len(), 12);
+        assert!(v[0].solutions[0].len() <= 2);
+        // A solved block has the empty solution.
+        let m = matrix::of_seq(&crate::flatten(crate::parser::parse("R U R'").unwrap().1));
+        let v = check(&m, BlockKind::F2lMinus1, 3);
+        assert_eq!(v[0].block, Block::F2lMinus1(Surface::D, 4));
+        assert_eq!(v[0].solutions, vec![vec![]]);
+        assert!(solve(&PermutationMatrix::identity(), BlockKind::B2x2x2, 0)
+            .iter()
+            .all(|x| x.solutions == vec![vec![]]));
+    }
+}
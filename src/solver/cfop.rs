@@ -0,0 +1,270 @@
+//! CFOP solver which solves like a human.
+//!
+//! The cross is solved optimally, the pairs are solved one by one by `f2l`
+//! and the last layer by the algorithms of `cfop::OLL_LIST` and `cfop::PLL_LIST`.
+
+use super::{auf_command, cross, f2l, move_count, Progress, Solution};
+use crate::cfop::{self, f2l_solved, recognize_oll, recognize_pll, with_auf, OLL_LIST, PLL_LIST};
+use crate::coord::Surface;
+use crate::cubie::{self, corner_facelets, edge_facelets};
+use crate::matrix::{self, PermutationMatrix};
+use crate::{Command, Move};
+
+/// How to choose the next pair.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairOrder {
+    /// The pair with the fewest moves.
+    Shortest,
+    /// The pair with the fewest moves together with the best next pair.
+    Lookahead,
+    /// FR, FL, BL and BR of the current orientation.
+    Fixed,
+}
+
+/// How to solve the last layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LastLayer {
+    /// One algorithm for each of OLL and PLL.
+    Full,
+    /// Edges then corners for OLL and corners then edges for PLL.
+    TwoLook,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The color of the cross.
+    pub color: Surface,
+    pub pair_order: PairOrder,
+    pub last_layer: LastLayer,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            color: Surface::D,
+            pair_order: PairOrder::Shortest,
+            last_layer: LastLayer::Full,
+        }
+    }
+}
+
+/// Orients the edges of the horizontal line shape.
+const OLL_LINE: &str = "FRUR'U'F'";
+/// Orients the edges of the L shape at the back left.
+const OLL_L: &str = "fRUR'U'f'";
+
+fn pll_alg(name: &str) -> &'static str {
+    PLL_LIST.iter().find(|x| x.0 == name).unwrap().1
+}
+
+fn is_home(m: &PermutationMatrix, facelet: u8) -> bool {
+    let center = facelet / 9 * 9 + 4;
+    m.inv_perm[facelet as usize] / 9 == m.inv_perm[center as usize] / 9
+}
+fn edges_oriented(m: &PermutationMatrix) -> bool {
+    (0..4).all(|i| is_home(m, edge_facelets(i)[0]))
+}
+/// The corners of the U layer are solved up to the AUF.
+fn corners_permuted(m: &PermutationMatrix) -> bool {
+    (0..4).any(|k| {
+        let m = matrix::of_seq(&[Command(Move::U, k)]) * *m;
+        (0..4).all(|i| corner_facelets(i).iter().all(|&f| is_home(&m, f)))
+    })
+}
+
+/// The fewest algorithms with the AUFs to reach the goal.
+fn combine(
+    m: &PermutationMatrix,
+    algs: &[&str],
+    depth: usize,
+    goal: &impl Fn(&PermutationMatrix) -> bool,
+) -> Option<Vec<Command>> {
+    if goal(m) {
+        return Some(vec![]);
+    }
+    if depth == 0 {
+        return None;
+    }
+    let mut best: Option<Vec<Command>> = None;
+    for alg in algs {
+        for auf in 0..4 {
+            let cs = with_auf(alg, auf, 0);
+            let next = matrix::of_seq(&cs) * *m;
+            if let Some(rest) = combine(&next, algs, depth - 1, goal) {
+                let mut cs = cs;
+                cs.extend(rest);
+                if best
+                    .as_ref()
+                    .is_none_or(|b| move_count(&cs) < move_count(b))
+                {
+                    best = Some(cs);
+                }
+            }
+        }
+    }
+    best
+}
+
+fn next_pair(m: &PermutationMatrix, order: PairOrder) -> Option<f2l::PairSolution> {
+    match order {
+        PairOrder::Shortest => f2l::solve(m).into_iter().next(),
+        PairOrder::Lookahead => f2l::solve(m).into_iter().min_by_key(|x| {
+            let next = matrix::of_seq(&x.moves) * *m;
+            let rest = f2l::solve(&next).first().map_or(0, |y| y.move_count());
+            x.move_count() + rest
+        }),
+        PairOrder::Fixed => f2l::SLOT_LIST
+            .iter()
+            .find_map(|&s| f2l::solve_slot(m, s).into_iter().next()),
+    }
+}
+
+/// Solve the cube by CFOP.
+///
+/// `None` if the state is broken or a pair can't be solved.
+pub fn solve(m: &PermutationMatrix, opts: &Options) -> Option<Solution> {
+    let o = cubie::Oriented::new(m)?;
+    if !o.cube.is_solvable() {
+        return None;
+    }
+    let mut s = Progress::new(m);
+
+    // Bring the color of the cross to D.
+    let rot = cubie::rotations()
+        .iter()
+        .find(|r| (matrix::of_seq(r) * *m).inv_perm[31] / 9 == opts.color as u8)
+        .unwrap();
+    let x = cross::solve(&(matrix::of_seq(rot) * *m), opts.color, 0)?;
+    let mut moves = rot.clone();
+    moves.extend(&x.solutions[0]);
+    s.push("Cross", moves);
+
+    for n in 1..=4 {
+        if f2l_solved(&s.m) {
+            break;
+        }
+        let pair = next_pair(&s.m, opts.pair_order)?;
+        s.push(&format!("F2L {}", n), pair.moves);
+    }
+
+    match opts.last_layer {
+        LastLayer::Full => {
+            let moves = match recognize_oll(&s.m) {
+                Some((i, auf)) => with_auf(OLL_LIST[i], auf, 0),
+                None => vec![],
+            };
+            s.push("OLL", moves);
+            let moves = match recognize_pll(&s.m) {
+                Some((name, pre, post)) => with_auf(pll_alg(name), pre, post),
+                None => vec![],
+            };
+            s.push("PLL", moves);
+        }
+        LastLayer::TwoLook => {
+            // Dot, L and line shapes.
+            let algs = [OLL_LINE, OLL_L];
+            let moves = combine(&s.m, &algs, 2, &edges_oriented)?;
+            s.push("OLL edges", moves);
+            let moves = match recognize_oll(&s.m) {
+                Some((i, auf)) => with_auf(OLL_LIST[i], auf, 0),
+                None => vec![],
+            };
+            s.push("OLL corners", moves);
+            // Adjacent and diagonal swaps.
+            let algs = [pll_alg("T"), pll_alg("Y")];
+            let moves = combine(&s.m, &algs, 1, &corners_permuted)?;
+            s.push("PLL corners", moves);
+            let moves = match recognize_pll(&s.m) {
+                Some((name, pre, post)) => with_auf(pll_alg(name), pre, post),
+                None => vec![],
+            };
+            s.push("PLL edges", moves);
+        }
+    }
+
+    let auf = (0..4).find(|&k| cfop::solved(&(matrix::of_seq(&[Command(Move::U, k)]) * s.m)))?;
+    s.push("AUF", auf_command(auf).into_iter().collect());
+    Some(s.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord::SURFACE_LIST;
+    use crate::cubie::CubieCube;
+
+    fn check(scramble: &[Command], opts: &Options) -> Solution {
+        let m = matrix::of_seq(scramble);
+        let sol = solve(&m, opts).unwrap();
+        assert!(cfop::solved(&(matrix::of_seq(&sol.moves()) * m)));
+        sol
+    }
+    #[test]
+    fn test_solve() {
+        let opts = Options::default();
+        for _ in 0..1500 {
+            let sol = check(&crate::random(25), &opts);
+            let names: Vec<&str> = sol.steps.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(names[0], "Cross");
+            assert_eq!(names[names.len() - 3..], ["OLL", "PLL", "AUF"]);
+            assert!(sol.steps[0].move_count() <= 8);
+        }
+    }
+    #[test]
+    fn test_broken() {
+        let opts = Options::default();
+        let mut x = CubieCube::identity();
+        x.co[0] = 1;
+        assert!(solve(&x.to_matrix(), &opts).is_none());
+        let mut x = CubieCube::identity();
+        x.eo[0] = 1;
+        assert!(solve(&x.to_matrix(), &opts).is_none());
+    }
+    #[test]
+    fn test_solved() {
+        let sol = solve(&PermutationMatrix::identity(), &Options::default()).unwrap();
+        assert_eq!(sol.move_count(), 0);
+        assert_eq!(sol.steps.len(), 4);
+    }
+    #[test]
+    fn test_options() {
+        for color in SURFACE_LIST {
+            for pair_order in [PairOrder::Shortest, PairOrder::Lookahead, PairOrder::Fixed] {
+                for last_layer in [LastLayer::Full, LastLayer::TwoLook] {
+                    let opts = Options {
+                        color,
+                        pair_order,
+                        last_layer,
+                    };
+                    for _ in 0..5 {
+                        check(&crate::random(25), &opts);
+                    }
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_oll_edges() {
+        assert!(OLL_LIST.contains(&OLL_LINE) && OLL_LIST.contains(&OLL_L));
+    }
+    #[test]
+    fn test_two_look() {
+        let opts = Options {
+            last_layer: LastLayer::TwoLook,
+            ..Options::default()
+        };
+        for _ in 0..500 {
+            let sol = check(&crate::random(25), &opts);
+            let names: Vec<&str> = sol.steps.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(
+                names[names.len() - 5..],
+                [
+                    "OLL edges",
+                    "OLL corners",
+                    "PLL corners",
+                    "PLL edges",
+                    "AUF"
+                ]
+            );
+        }
+    }
+}
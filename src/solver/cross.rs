@@ -0,0 +1,201 @@
+//! Cross solver for any color.
+//!
+//! Only the four cross edges are searched. The distance table of the D cross
+//! is exact, so all optimal and near-optimal solutions are enumerated quickly.
+//! The crosses of the other colors are solved in the frame where the color is on D.
+
+use super::follows;
+use super::optimal::{Pattern, PatternDb};
+use super::table;
+use crate::coord::{Surface, SURFACE_LIST};
+use crate::cubie::{self, conjugate, CubieCube, Oriented, N_MOVE};
+use crate::matrix::PermutationMatrix;
+use crate::{Command, Move};
+use once_cell::sync::OnceCell;
+use std::path::Path;
+
+/// Solutions of the cross of a color.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cross {
+    /// The color is the color of the center of the surface in the solved state.
+    pub color: Surface,
+    /// The length of the optimal solutions.
+    pub distance: usize,
+    /// Sorted by the length.
+    pub solutions: Vec<Vec<Command>>,
+}
+
+/// The D cross edges: DR, DF, DL and DB.
+static TABLE: OnceCell<PatternDb> = OnceCell::new();
+
+fn pattern() -> Pattern {
+    Pattern::Edges(vec![4, 5, 6, 7])
+}
+fn table() -> &'static PatternDb {
+    TABLE.get_or_init(|| PatternDb::new(pattern()))
+}
+
+/// Generate the table in advance. Otherwise it is generated on the first solve.
+pub fn init() {
+    table();
+}
+
+/// `init` with the table saved in the directory.
+///
+/// The table is generated and saved only if the file doesn't exist.
+/// Nothing is done if the table is already generated.
+pub fn init_from(dir: &Path) -> Result<(), table::Error> {
+    if TABLE.get().is_some() {
+        return Ok(());
+    }
+    let db = PatternDb::load_or_new(pattern(), &PatternDb::path_in(&pattern(), dir))?;
+    let _ = TABLE.set(db);
+    Ok(())
+}
+
+fn face_of(s: Surface) -> Move {
+    match s {
+        Surface::R => Move::R,
+        Surface::L => Move::L,
+        Surface::U => Move::U,
+        Surface::D => Move::D,
+        Surface::F => Move::F,
+        Surface::B => Move::B,
+    }
+}
+/// The rotation of the frame where the color is on D.
+fn frame_of(color: Surface) -> usize {
+    (0..cubie::rotations().len())
+        .find(|&k| cubie::relabel(Command(Move::D, 1), k).0 == face_of(color))
+        .unwrap()
+}
+
+fn search(x: &CubieCube, togo: usize, path: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+    let d = table().distance(x);
+    if togo == 0 {
+        if d == 0 {
+            out.push(path.clone());
+        }
+        return;
+    }
+    // Solutions shouldn't pass through the solved cross.
+    if d == 0 && !path.is_empty() {
+        return;
+    }
+    for m in 0..N_MOVE {
+        if !follows(path.last().copied(), m) {
+            continue;
+        }
+        let y = x.apply(m);
+        if table().distance(&y) > togo - 1 {
+            continue;
+        }
+        path.push(m);
+        search(&y, togo - 1, path, out);
+        path.pop();
+    }
+}
+
+/// Solve the cross of the color.
+///
+/// Solutions up to `extra` moves longer than the optimal are returned.
+/// `None` if the state is broken.
+pub fn solve(m: &PermutationMatrix, color: Surface, extra: usize) -> Option<Cross> {
+    let o = Oriented::new(m)?;
+    let frame = frame_of(color);
+    let x = conjugate(&o.cube, frame);
+    let distance = table().distance(&x);
+    let mut out = vec![];
+    if distance == 0 {
+        out.push(vec![]);
+    } else {
+        for len in distance..=distance + extra {
+            search(&x, len, &mut vec![], &mut out);
+        }
+    }
+    let solutions = out
+        .into_iter()
+        .map(|path| {
+            let cs: Vec<Command> = path
+                .into_iter()
+                .map(|m| cubie::relabel(cubie::command_of(m), frame))
+                .collect();
+            o.relabel(&cs)
+        })
+        .collect();
+    Some(Cross {
+        color,
+        distance,
+        solutions,
+    })
+}
+
+/// Solve the crosses of all six colors.
+pub fn solve_all(m: &PermutationMatrix, extra: usize) -> Option<Vec<Cross>> {
+    SURFACE_LIST.iter().map(|&c| solve(m, c, extra)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::cross_solved;
+    use crate::flatten;
+    use crate::matrix;
+    use crate::parser::parse;
+
+    /// Rotate the whole cube to bring the color to D.
+    fn cross_solved_on(m: &PermutationMatrix, color: Surface) -> bool {
+        cubie::rotations().iter().any(|r| {
+            let m = matrix::of_seq(r) * *m;
+            m.inv_perm[31] / 9 == color as u8 && cross_solved(&m)
+        })
+    }
+    #[test]
+    fn test_solve() {
+        let cs = flatten(parse("R F'").unwrap().1);
+        let x = solve(&matrix::of_seq(&cs), Surface::D, 0).unwrap();
+        assert_eq!(x.distance, 2);
+        assert_eq!(x.solutions, vec![flatten(parse("F R'").unwrap().1)]);
+        let x = solve(&matrix::of_seq(&[Command(Move::D, 1)]), Surface::U, 0).unwrap();
+        assert_eq!(x.distance, 0);
+        assert_eq!(x.solutions, vec![vec![]]);
+        // The centers are moved by E.
+        let cs = flatten(parse("D2 E").unwrap().1);
+        let x = solve(&matrix::of_seq(&cs), Surface::U, 0).unwrap();
+        assert_eq!(x.distance, 1);
+        let mut seq = cs.clone();
+        seq.extend(&x.solutions[0]);
+        assert!(cross_solved_on(&matrix::of_seq(&seq), Surface::U));
+    }
+    #[test]
+    fn test_solve_all() {
+        for _ in 0..3 {
+            let scramble = crate::random(25);
+            let crosses = solve_all(&matrix::of_seq(&scramble), 1).unwrap();
+            assert_eq!(crosses.len(), 6);
+            for x in crosses {
+                assert!(x.distance <= 8);
+                assert!(!x.solutions.is_empty());
+                let mut prev = 0;
+                for (i, sol) in x.solutions.iter().enumerate() {
+                    assert!(sol.len() >= x.distance && sol.len() <= x.distance + 1);
+                    assert!(sol.len() >= prev);
+                    prev = sol.len();
+                    assert!(!x.solutions[i + 1..].contains(sol));
+                    let mut seq = scramble.clone();
+                    seq.extend(sol);
+                    assert!(cross_solved_on(&matrix::of_seq(&seq), x.color));
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_path() {
+        // Shared with the database of `optimal::Solver`.
+        let dir = Path::new("tables");
+        assert_eq!(
+            PatternDb::path_in(&pattern(), dir),
+            dir.join("edges-4-5-6-7.bin")
+        );
+    }
+}
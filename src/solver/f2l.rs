@@ -0,0 +1,349 @@
+//! F2L pair solver.
+//!
+//! A pair is recognized by the positions of its corner and edge relative to the centers
+//! and solved by the matching `cfop::F2L_LIST` entry after the rotation and AUF.
+//! If a piece is trapped in another slot, short extractions that keep the cross
+//! and the solved slots are searched first.
+//!
+//! The cross should be solved on D.
+
+use super::{auf_command, follows, move_count};
+use crate::cfop::{cross_solved, F2L_LIST};
+use crate::cubie::{self, corner_facelets, edge_facelets, N_MOVE};
+use crate::matrix::{self, PermutationMatrix};
+use crate::{flatten, parser, Command, Move};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// F2L slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Slot {
+    FR,
+    FL,
+    BL,
+    BR,
+}
+pub const SLOT_LIST: [Slot; 4] = [Slot::FR, Slot::FL, Slot::BL, Slot::BR];
+impl Slot {
+    /// The rotation to bring the slot to FR.
+    pub fn rotation(self) -> Option<Command> {
+        match self {
+            Slot::FR => None,
+            Slot::FL => Some(Command(Move::y, -1)),
+            Slot::BL => Some(Command(Move::y, 2)),
+            Slot::BR => Some(Command(Move::y, 1)),
+        }
+    }
+    /// The corner and edge positions in the order of `cubie`.
+    fn positions(self) -> (usize, usize) {
+        match self {
+            Slot::FR => (4, 8),
+            Slot::FL => (5, 9),
+            Slot::BL => (6, 10),
+            Slot::BR => (7, 11),
+        }
+    }
+}
+
+/// A way to solve a pair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PairSolution {
+    pub slot: Slot,
+    /// Index of `cfop::F2L_LIST`.
+    pub case: usize,
+    /// Moves to take the pieces out of the other slots. Empty if nothing is trapped.
+    pub extraction: Vec<Command>,
+    /// The number of U before the algorithm.
+    pub auf: i8,
+    /// The rotation, extraction, AUF and algorithm.
+    pub moves: Vec<Command>,
+}
+impl PairSolution {
+    /// The number of moves without rotations.
+    pub fn move_count(&self) -> usize {
+        move_count(&self.moves)
+    }
+}
+
+const R: usize = 0;
+const D: usize = 3;
+const F: usize = 4;
+
+fn color(m: &PermutationMatrix, facelet: u8) -> u8 {
+    m.inv_perm[facelet as usize] / 9
+}
+fn center_color(m: &PermutationMatrix, face: usize) -> u8 {
+    color(m, 9 * face as u8 + 4)
+}
+fn is_home(m: &PermutationMatrix, facelet: u8) -> bool {
+    color(m, facelet) == center_color(m, facelet as usize / 9)
+}
+/// Check if the pair is solved in the slot.
+pub fn slot_solved(m: &PermutationMatrix, slot: Slot) -> bool {
+    let (c, e) = slot.positions();
+    corner_facelets(c).iter().all(|&f| is_home(m, f))
+        && edge_facelets(e).iter().all(|&f| is_home(m, f))
+}
+
+/// Positions and orientations of the FR pair. Corner first.
+type Signature = (u8, u8, u8, u8);
+
+fn signature(m: &PermutationMatrix) -> Signature {
+    let (d, f, r) = (center_color(m, D), center_color(m, F), center_color(m, R));
+    let mut sig = (0, 0, 0, 0);
+    for i in 0..8 {
+        let cs = corner_facelets(i).map(|x| color(m, x));
+        if cs.contains(&d) && cs.contains(&f) && cs.contains(&r) {
+            let ori = cs.iter().position(|&c| c == d).unwrap();
+            sig.0 = i as u8;
+            sig.1 = ori as u8;
+        }
+    }
+    for i in 0..12 {
+        let cs = edge_facelets(i).map(|x| color(m, x));
+        if cs.contains(&f) && cs.contains(&r) {
+            let ori = cs.iter().position(|&c| c == f).unwrap();
+            sig.2 = i as u8;
+            sig.3 = ori as u8;
+        }
+    }
+    sig
+}
+/// The number of pieces of the FR pair not trapped in the other slots.
+fn free_count(sig: Signature) -> usize {
+    let corner = sig.0 <= 4;
+    let edge = sig.2 < 4 || sig.2 == 8;
+    corner as usize + edge as usize
+}
+
+/// Cases and AUFs for each signature of the FR pair.
+static CASES: Lazy<HashMap<Signature, Vec<(usize, i8)>>> = Lazy::new(|| {
+    let mut tbl: HashMap<Signature, Vec<(usize, i8)>> = HashMap::new();
+    for (i, alg) in F2L_LIST.iter().enumerate() {
+        let alg = flatten(parser::parse(alg).unwrap().1);
+        for auf in 0..4 {
+            let mut seq: Vec<Command> = auf_command(auf).into_iter().collect();
+            seq.extend(&alg);
+            let inv: Vec<Command> = seq.iter().rev().map(|c| c.prime()).collect();
+            let v = tbl.entry(signature(&matrix::of_seq(&inv))).or_default();
+            if v.iter().all(|x| x.0 != i) {
+                v.push((i, auf));
+            }
+        }
+    }
+    tbl
+});
+
+/// Recognize the case of the pair of the slot and the AUF to use.
+///
+/// Empty if a piece is trapped in another slot.
+pub fn recognize(m: &PermutationMatrix, slot: Slot) -> Vec<(usize, i8)> {
+    let m = rotate(m, slot);
+    CASES.get(&signature(&m)).cloned().unwrap_or_default()
+}
+fn rotate(m: &PermutationMatrix, slot: Slot) -> PermutationMatrix {
+    let rot: Vec<Command> = slot.rotation().into_iter().collect();
+    matrix::of_seq(&rot) * *m
+}
+
+fn solution(slot: Slot, extraction: Vec<Command>, case: usize, auf: i8) -> PairSolution {
+    let mut moves: Vec<Command> = slot.rotation().into_iter().collect();
+    moves.extend(&extraction);
+    moves.extend(auf_command(auf));
+    moves.extend(flatten(parser::parse(F2L_LIST[case]).unwrap().1));
+    PairSolution {
+        slot,
+        case,
+        extraction,
+        auf,
+        moves,
+    }
+}
+
+static MOVES: Lazy<Vec<PermutationMatrix>> = Lazy::new(|| {
+    (0..N_MOVE)
+        .map(|k| matrix::of_seq(&[cubie::command_of(k)]))
+        .collect()
+});
+
+/// Moves that don't break the cross and the protected slots and free more pieces of the pair.
+fn extractions(
+    m: &PermutationMatrix,
+    protected: &[Slot],
+) -> Vec<(Vec<Command>, PermutationMatrix)> {
+    fn dfs(
+        m: &PermutationMatrix,
+        path: &mut Vec<usize>,
+        free: usize,
+        protected: &[Slot],
+        out: &mut Vec<(Vec<Command>, PermutationMatrix)>,
+    ) {
+        if !path.is_empty()
+            && cross_solved(m)
+            && protected.iter().all(|&s| slot_solved(m, s))
+            && free_count(signature(m)) > free
+        {
+            out.push((path.iter().map(|&k| cubie::command_of(k)).collect(), *m));
+            return;
+        }
+        if path.len() == 3 {
+            return;
+        }
+        for k in 0..N_MOVE {
+            // D turns never keep the cross in a short extraction.
+            if k / 3 == 3 || !follows(path.last().copied(), k) {
+                continue;
+            }
+            let next = MOVES[k] * *m;
+            path.push(k);
+            dfs(&next, path, free, protected, out);
+            path.pop();
+        }
+    }
+    let mut out = vec![];
+    dfs(
+        m,
+        &mut vec![],
+        free_count(signature(m)),
+        protected,
+        &mut out,
+    );
+    out
+}
+
+/// Solve the pair of the slot. The options are sorted by the move count.
+///
+/// Empty if the cross is not solved, the slot is already solved or no extraction is found.
+pub fn solve_slot(m: &PermutationMatrix, slot: Slot) -> Vec<PairSolution> {
+    if !cross_solved(m) || slot_solved(m, slot) {
+        return vec![];
+    }
+    let r = rotate(m, slot);
+    // The other slots seen from the rotated cube.
+    let protected: Vec<Slot> = SLOT_LIST[1..]
+        .iter()
+        .copied()
+        .filter(|&s| slot_solved(&r, s))
+        .collect();
+    let mut v = vec![];
+    let mut frontier = vec![(vec![], r)];
+    while !frontier.is_empty() && v.is_empty() {
+        let mut next = vec![];
+        for (ext, x) in frontier {
+            match CASES.get(&signature(&x)) {
+                Some(cases) => {
+                    for &(case, auf) in cases {
+                        v.push(solution(slot, ext.clone(), case, auf));
+                    }
+                }
+                None => {
+                    for (e, y) in extractions(&x, &protected) {
+                        let mut ext = ext.clone();
+                        ext.extend(e);
+                        next.push((ext, y));
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+    v.sort_by_key(|x| x.move_count());
+    v.dedup_by(|a, b| a.moves == b.moves);
+    v
+}
+
+/// Solve the pairs of all unsolved slots. The options are sorted by the move count.
+pub fn solve(m: &PermutationMatrix) -> Vec<PairSolution> {
+    let mut v: Vec<PairSolution> = SLOT_LIST.iter().flat_map(|&s| solve_slot(m, s)).collect();
+    v.sort_by_key(|x| x.move_count());
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::{f2l_solved, PLL_LIST};
+
+    fn parse(s: &str) -> Vec<Command> {
+        flatten(parser::parse(s).unwrap().1)
+    }
+    fn inverse(cs: &[Command]) -> Vec<Command> {
+        cs.iter().rev().map(|c| c.prime()).collect()
+    }
+    #[test]
+    fn test_recognize_all() {
+        // Every configuration of the pair out of the other slots is a case.
+        let mut n = 0;
+        for c in [0, 1, 2, 3, 4] {
+            for e in [0, 1, 2, 3, 8] {
+                for co in 0..3 {
+                    for eo in 0..2 {
+                        if (c, co, e, eo) == (4, 0, 8, 0) {
+                            continue;
+                        }
+                        assert!(CASES.contains_key(&(c, co, e, eo)));
+                        n += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(CASES.len(), n);
+    }
+    #[test]
+    fn test_solve_case() {
+        for (i, alg) in F2L_LIST.iter().enumerate() {
+            for slot in SLOT_LIST {
+                let auf = (i % 4) as i8;
+                let rot: Vec<Command> = slot.rotation().into_iter().collect();
+                let mut problem: Vec<Command> = auf_command(auf).into_iter().collect();
+                problem.extend(parse(alg));
+                let mut seq = parse(PLL_LIST[i % PLL_LIST.len()].1);
+                seq.extend(&rot);
+                seq.extend(inverse(&problem));
+                seq.extend(inverse(&rot));
+                let m = matrix::of_seq(&seq);
+                assert!(recognize(&m, slot).iter().any(|x| x.0 == i));
+
+                let v = solve_slot(&m, slot);
+                assert!(!v.is_empty());
+                for x in &v {
+                    assert!(x.extraction.is_empty());
+                    assert!(f2l_solved(&(matrix::of_seq(&x.moves) * m)));
+                }
+                assert!(v.windows(2).all(|w| w[0].move_count() <= w[1].move_count()));
+            }
+        }
+    }
+    #[test]
+    fn test_solve_trapped() {
+        let triggers = [
+            "RUR'", "R'U'R", "L'U'L", "LUL'", "BUB'", "B'U'B", "F'U'F", "FUF'", "U",
+        ];
+        for _ in 0..10 {
+            let mut seq = vec![];
+            for _ in 0..10 {
+                seq.extend(parse(triggers[rand::random::<usize>() % triggers.len()]));
+            }
+            let mut m = matrix::of_seq(&seq);
+            for _ in 0..4 {
+                if f2l_solved(&m) {
+                    break;
+                }
+                let solved: Vec<Slot> = SLOT_LIST
+                    .iter()
+                    .copied()
+                    .filter(|&s| slot_solved(&m, s))
+                    .collect();
+                let v = solve(&m);
+                let x = &v[0];
+                assert!(v.iter().all(|y| x.move_count() <= y.move_count()));
+                m = matrix::of_seq(&x.moves) * m;
+                assert!(cross_solved(&m));
+                // The slots are relabeled by the rotation in the solution.
+                let n = SLOT_LIST.iter().filter(|&&s| slot_solved(&m, s)).count();
+                assert_eq!(n, solved.len() + 1);
+            }
+            assert!(f2l_solved(&m));
+        }
+    }
+}
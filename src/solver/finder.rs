@@ -0,0 +1,371 @@
+//! Algorithm finder with a restricted set of moves.
+//!
+//! All the algorithms up to a length are enumerated by iterative deepening,
+//! so the results are complete and come in a canonical order:
+//! shorter ones first and then in the order of the generators and the turns `1, 2, -1`.
+//!
+//! Redundant sequences are pruned. A move never follows the same move
+//! and moves on the same axis like R and L commute so they are kept in the order of the generators.
+//!
+//! The search runs in parallel by splitting the work at the first moves.
+//! The results are sorted so they don't depend on the number of threads.
+
+use super::{par_map, turn};
+use crate::cubie;
+use crate::matrix::{self, PermutationMatrix};
+use crate::pattern::Goal;
+use crate::{axis, Command, Move};
+
+/// The depth of the first moves to split the work among the threads.
+const SPLIT_DEPTH: usize = 2;
+
+/// What the algorithms should do.
+pub enum Target<'a> {
+    /// The effect of the algorithm on the solved cube, i.e. `matrix::of_seq(alg) == m`.
+    Effect(PermutationMatrix),
+    /// The algorithm should bring the state to one satisfying the predicate.
+    Solve(
+        PermutationMatrix,
+        &'a (dyn Fn(&PermutationMatrix) -> bool + Sync),
+    ),
+    /// The algorithm should bring the state to one meeting the goal.
+    Goal(PermutationMatrix, &'a Goal),
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The moves to use like R and U for <R, U>.
+    pub generators: Vec<Move>,
+    /// The maximum number of moves without the AUFs and the rotation.
+    pub max_len: usize,
+    /// Allow U turns before and after the algorithm.
+    pub auf: bool,
+    /// Allow a whole cube rotation after the algorithm.
+    pub rotation: bool,
+    /// Stop after this number of algorithms.
+    pub limit: Option<usize>,
+    /// The number of threads. The results are the same for any number.
+    pub threads: usize,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            generators: vec![Move::R, Move::U],
+            max_len: 8,
+            auf: false,
+            rotation: false,
+            limit: None,
+            threads: 1,
+        }
+    }
+}
+
+/// A found algorithm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Found {
+    /// The U turns before the algorithm.
+    pub pre_auf: i8,
+    /// The moves in the generators.
+    pub moves: Vec<Command>,
+    /// The U turns after the algorithm.
+    pub post_auf: i8,
+    /// The whole cube rotation after the algorithm.
+    pub rotation: Vec<Command>,
+}
+impl Found {
+    /// The whole sequence with the AUFs and the rotation.
+    pub fn sequence(&self) -> Vec<Command> {
+        let mut v: Vec<Command> = turn(Move::U, self.pre_auf).into_iter().collect();
+        v.extend(&self.moves);
+        v.extend(turn(Move::U, self.post_auf));
+        v.extend(&self.rotation);
+        v
+    }
+}
+
+struct Finder<'a> {
+    moves: Vec<(usize, Command, PermutationMatrix)>,
+    goal: &'a (dyn Fn(&PermutationMatrix) -> bool + Sync),
+    posts: Vec<(i8, PermutationMatrix)>,
+    rotations: Vec<(usize, PermutationMatrix)>,
+    /// The first and the last moves can't be U because the AUF covers them.
+    no_u_ends: bool,
+}
+impl Finder<'_> {
+    fn follows(&self, prev: Option<usize>, m: usize) -> bool {
+        match prev {
+            None => true,
+            Some(p) => {
+                let ((gp, cp, _), (gm, cm, _)) = (&self.moves[p], &self.moves[m]);
+                gp != gm && !(axis(cp.0) == axis(cm.0) && gp > gm)
+            }
+        }
+    }
+    /// The AUF and the rotation to reach the goal.
+    fn check(&self, x: &PermutationMatrix) -> Option<(i8, usize)> {
+        for (b, u) in &self.posts {
+            let y = *u * *x;
+            for (k, r) in &self.rotations {
+                if (self.goal)(&(*r * y)) {
+                    return Some((*b, *k));
+                }
+            }
+        }
+        None
+    }
+    /// All the canonical sequences of the length with the states.
+    fn prefixes(
+        &self,
+        x: &PermutationMatrix,
+        togo: usize,
+        path: &mut Vec<usize>,
+        f: &mut impl FnMut(Vec<usize>, PermutationMatrix),
+    ) {
+        if togo == 0 {
+            f(path.clone(), *x);
+            return;
+        }
+        for m in 0..self.moves.len() {
+            if !self.follows(path.last().copied(), m) {
+                continue;
+            }
+            path.push(m);
+            self.prefixes(&(self.moves[m].2 * *x), togo - 1, path, f);
+            path.pop();
+        }
+    }
+    fn dfs(
+        &self,
+        x: &PermutationMatrix,
+        togo: usize,
+        path: &mut Vec<usize>,
+        out: &mut Vec<(Vec<usize>, i8, usize)>,
+    ) {
+        if togo == 0 {
+            let is_u = |m: Option<&usize>| m.is_some_and(|&m| self.moves[m].1 .0 == Move::U);
+            if self.no_u_ends && (is_u(path.first()) || is_u(path.last())) {
+                return;
+            }
+            if let Some((b, k)) = self.check(x) {
+                out.push((path.clone(), b, k));
+            }
+            return;
+        }
+        for m in 0..self.moves.len() {
+            if !self.follows(path.last().copied(), m) {
+                continue;
+            }
+            path.push(m);
+            self.dfs(&(self.moves[m].2 * *x), togo - 1, path, out);
+            path.pop();
+        }
+    }
+}
+
+/// Enumerate the algorithms for the target in the canonical order.
+pub fn find(target: &Target, opts: &Options) -> Vec<Found> {
+    let identity = PermutationMatrix::identity();
+    let is_identity = |x: &PermutationMatrix| *x == identity;
+    let check_goal;
+    type Pred<'a> = &'a (dyn Fn(&PermutationMatrix) -> bool + Sync);
+    let (start, goal): (PermutationMatrix, Pred) = match target {
+        Target::Effect(m) => (m.inv(), &is_identity),
+        Target::Solve(m, goal) => (*m, *goal),
+        Target::Goal(m, goal) => {
+            check_goal = move |x: &PermutationMatrix| goal.check(x);
+            (*m, &check_goal)
+        }
+    };
+    let mut moves = vec![];
+    for (g, &mov) in opts.generators.iter().enumerate() {
+        for k in [1, 2, -1] {
+            let c = Command(mov, k);
+            moves.push((g, c, matrix::of_seq(&[c])));
+        }
+    }
+    let aufs: Vec<(i8, PermutationMatrix)> = (0..if opts.auf { 4 } else { 1 })
+        .map(|k| (k, matrix::of_seq(&[Command(Move::U, k)])))
+        .collect();
+    let rotations: Vec<(usize, PermutationMatrix)> = if opts.rotation {
+        cubie::rotations()
+            .iter()
+            .enumerate()
+            .map(|(k, r)| (k, matrix::of_seq(r)))
+            .collect()
+    } else {
+        vec![(0, identity)]
+    };
+    let finder = Finder {
+        moves,
+        goal,
+        posts: aufs.clone(),
+        rotations,
+        no_u_ends: opts.auf && opts.generators.contains(&Move::U),
+    };
+
+    let mut v = vec![];
+    for len in 0..=opts.max_len {
+        // The work is split by the first moves.
+        let split = len.min(SPLIT_DEPTH);
+        let mut tasks = vec![];
+        for (a, u) in &aufs {
+            finder.prefixes(&(*u * start), split, &mut vec![], &mut |p, x| {
+                tasks.push((*a, p, x))
+            });
+        }
+        let found = par_map(&tasks, opts.threads, |(a, p, x)| {
+            let mut found = vec![];
+            finder.dfs(x, len - p.len(), &mut p.clone(), &mut found);
+            found
+                .into_iter()
+                .map(|(p, b, k)| (p, *a, b, k))
+                .collect::<Vec<_>>()
+        });
+        let mut out: Vec<_> = found.into_iter().flatten().collect();
+        out.sort();
+        for (p, a, b, k) in out {
+            v.push(Found {
+                pre_auf: a,
+                moves: p.into_iter().map(|m| finder.moves[m].1).collect(),
+                post_auf: b,
+                rotation: cubie::rotations()[k].clone(),
+            });
+            if opts.limit.is_some_and(|n| v.len() >= n) {
+                return v;
+            }
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop;
+
+    fn parse(s: &str) -> Vec<Command> {
+        crate::flatten(crate::parser::parse(s).unwrap().1)
+    }
+    fn check(target: &Target, opts: &Options) -> Vec<Found> {
+        let v = find(target, opts);
+        for (i, x) in v.iter().enumerate() {
+            assert!(x.moves.len() <= opts.max_len);
+            assert!(x.moves.iter().all(|c| opts.generators.contains(&c.0)));
+            assert!(x.moves.windows(2).all(|w| w[0].0 != w[1].0));
+            if i > 0 {
+                assert!(v[i - 1].moves.len() <= x.moves.len());
+            }
+            let m = matrix::of_seq(&x.sequence());
+            match target {
+                Target::Effect(e) => assert_eq!(m, *e),
+                Target::Solve(s, goal) => assert!(goal(&(m * *s))),
+                Target::Goal(s, goal) => assert!(goal.check(&(m * *s))),
+            }
+        }
+        v
+    }
+    #[test]
+    fn test_effect() {
+        let sune = parse("RUR'URU2R'");
+        let opts = Options {
+            max_len: 7,
+            ..Options::default()
+        };
+        let v = check(&Target::Effect(matrix::of_seq(&sune)), &opts);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].moves, sune);
+        // The AUFs are free.
+        let mut cs = parse("U");
+        cs.extend(&sune);
+        cs.push(Command(Move::U, 2));
+        let opts = Options { auf: true, ..opts };
+        let v = check(&Target::Effect(matrix::of_seq(&cs)), &opts);
+        assert!(v.iter().any(|x| x.moves == sune));
+        assert!(v.iter().all(|x| x.moves.len() == 7));
+        // <M, U>
+        let opts = Options {
+            generators: vec![Move::M, Move::U],
+            max_len: 4,
+            ..Options::default()
+        };
+        let v = check(&Target::Effect(matrix::of_seq(&parse("M'U2MU2"))), &opts);
+        assert_eq!(v[0].moves, parse("M'U2MU2"));
+    }
+    #[test]
+    fn test_canonical() {
+        // Every sequence of <R, L> up to 2 moves without redundancy.
+        let opts = Options {
+            generators: vec![Move::R, Move::L],
+            max_len: 2,
+            ..Options::default()
+        };
+        let v = check(
+            &Target::Solve(PermutationMatrix::identity(), &|_| true),
+            &opts,
+        );
+        assert_eq!(v.len(), 1 + 6 + 9);
+        assert_eq!(v[1].moves, parse("R"));
+        assert_eq!(v[2].moves, parse("R2"));
+        assert_eq!(v[3].moves, parse("R'"));
+        assert_eq!(v[7].moves, parse("RL"));
+        let v = find(
+            &Target::Solve(PermutationMatrix::identity(), &|_| true),
+            &Options {
+                limit: Some(5),
+                ..opts
+            },
+        );
+        assert_eq!(v.len(), 5);
+    }
+    #[test]
+    fn test_threads() {
+        let goal = crate::pattern::Goal::edges(&[1]);
+        let pred = |m: &PermutationMatrix| goal.check(m);
+        let opts = Options {
+            generators: vec![Move::R, Move::U, Move::F],
+            max_len: 4,
+            auf: true,
+            ..Options::default()
+        };
+        let mut n = 0;
+        for seed in 0..3 {
+            let m = matrix::of_seq(&crate::random_seeded(20, seed));
+            let target = Target::Solve(m, &pred);
+            let v = find(&target, &opts);
+            let w = find(
+                &target,
+                &Options {
+                    threads: 4,
+                    ..opts.clone()
+                },
+            );
+            assert_eq!(v, w);
+            n += v.len();
+        }
+        assert!(n > 0);
+    }
+    #[test]
+    fn test_solve() {
+        // OLL of the Sune case with <R, U, F>.
+        let case = matrix::of_seq(&parse("RUR'URU2R'"));
+        let opts = Options {
+            generators: vec![Move::R, Move::U, Move::F],
+            max_len: 7,
+            auf: true,
+            ..Options::default()
+        };
+        let v = check(&Target::Solve(case, &cfop::oll_solved), &opts);
+        assert!(!v.is_empty());
+        assert!(v.iter().any(|x| x.moves == parse("RU2R'U'RU'R'")));
+        // The rotation is free.
+        let opts = Options {
+            generators: vec![Move::R, Move::L],
+            max_len: 2,
+            rotation: true,
+            ..Options::default()
+        };
+        let v = check(&Target::Effect(matrix::of_seq(&parse("M"))), &opts);
+        assert_eq!(v[0].moves, parse("RL'"));
+        assert_eq!(matrix::of_seq(&v[0].rotation), matrix::of_seq(&parse("x'")));
+    }
+}
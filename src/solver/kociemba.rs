@@ -0,0 +1,516 @@
+//! Kociemba's two-phase algorithm.
+//!
+//! Phase 1 brings the cube into the subgroup <U, D, R2, L2, F2, B2>
+//! where the corners and edges are oriented and the E-slice edges are in the E-slice.
+//! Phase 2 solves the cube within the subgroup.
+//! After the first solution is found, the search continues with longer phase 1
+//! to find shorter solutions until the solution is short enough or the time runs out.
+
+use super::anytime::{AnytimeSolver, CancelToken, Control, Solutions, Stats, Stream};
+use super::table::{self, Data, U16s};
+use super::{follows, move_table, prune_table};
+use crate::cubie::{self, CubieCube, Oriented, N_MOVE};
+use crate::matrix::PermutationMatrix;
+use crate::Command;
+use once_cell::sync::OnceCell;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const N_TWIST: usize = 2187;
+const N_FLIP: usize = 2048;
+const N_SLICE: usize = 495;
+const N_SLICE_SORTED: usize = 11880;
+const N_PERM: usize = 40320;
+/// Longest solution to look for.
+const MAX_DEPTH: usize = 30;
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The search stops when a solution of this length or shorter is found.
+    pub max_length: usize,
+    /// The search stops after this time and returns the best solution so far.
+    pub timeout: Duration,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_length: 22,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Moves in the phase 2 subgroup.
+fn is_phase2_move(m: usize) -> bool {
+    let face = m / 3;
+    face == 0 || face == 3 || m % 3 == 1
+}
+fn phase2_moves() -> Vec<usize> {
+    (0..N_MOVE).filter(|&m| is_phase2_move(m)).collect()
+}
+
+struct Tables {
+    twist_move: U16s,
+    flip_move: U16s,
+    slice_sorted_move: U16s,
+    corners_move: U16s,
+    ud_edges_move: U16s,
+    slice_twist_prune: Data,
+    slice_flip_prune: Data,
+    corners_prune: Data,
+    ud_edges_prune: Data,
+}
+impl Tables {
+    fn new() -> Self {
+        let all: Vec<usize> = (0..N_MOVE).collect();
+        let p2 = phase2_moves();
+        let twist_move = move_table(N_TWIST, &all, CubieCube::set_twist, CubieCube::twist);
+        let flip_move = move_table(N_FLIP, &all, CubieCube::set_flip, CubieCube::flip);
+        let slice_sorted_move = move_table(
+            N_SLICE_SORTED,
+            &all,
+            CubieCube::set_slice_sorted,
+            CubieCube::slice_sorted,
+        );
+        let corners_move = move_table(N_PERM, &p2, CubieCube::set_corners, CubieCube::corners);
+        let ud_edges_move = move_table(N_PERM, &p2, CubieCube::set_ud_edges, CubieCube::ud_edges);
+
+        let slice_move = |s: usize, m: usize| slice_sorted_move[24 * s * N_MOVE + m] as usize / 24;
+        let slice_twist_prune = prune_table(N_SLICE * N_TWIST, 0, &all, |x, m| {
+            let (s, t) = (x / N_TWIST, x % N_TWIST);
+            slice_move(s, m) * N_TWIST + twist_move[t * N_MOVE + m] as usize
+        });
+        let slice_flip_prune = prune_table(N_SLICE * N_FLIP, 0, &all, |x, m| {
+            let (s, f) = (x / N_FLIP, x % N_FLIP);
+            slice_move(s, m) * N_FLIP + flip_move[f * N_MOVE + m] as usize
+        });
+        let corners_prune = prune_table(24 * N_PERM, 0, &p2, |x, m| {
+            let (s, c) = (x / N_PERM, x % N_PERM);
+            slice_sorted_move[s * N_MOVE + m] as usize * N_PERM
+                + corners_move[c * N_MOVE + m] as usize
+        });
+        let ud_edges_prune = prune_table(24 * N_PERM, 0, &p2, |x, m| {
+            let (s, e) = (x / N_PERM, x % N_PERM);
+            slice_sorted_move[s * N_MOVE + m] as usize * N_PERM
+                + ud_edges_move[e * N_MOVE + m] as usize
+        });
+        Self {
+            twist_move: U16s::new(&twist_move),
+            flip_move: U16s::new(&flip_move),
+            slice_sorted_move: U16s::new(&slice_sorted_move),
+            corners_move: U16s::new(&corners_move),
+            ud_edges_move: U16s::new(&ud_edges_move),
+            slice_twist_prune: Data::Owned(slice_twist_prune),
+            slice_flip_prune: Data::Owned(slice_flip_prune),
+            corners_prune: Data::Owned(corners_prune),
+            ud_edges_prune: Data::Owned(ud_edges_prune),
+        }
+    }
+    fn to_bytes(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.twist_move.bytes().to_vec(),
+            self.flip_move.bytes().to_vec(),
+            self.slice_sorted_move.bytes().to_vec(),
+            self.corners_move.bytes().to_vec(),
+            self.ud_edges_move.bytes().to_vec(),
+            self.slice_twist_prune.to_vec(),
+            self.slice_flip_prune.to_vec(),
+            self.corners_prune.to_vec(),
+            self.ud_edges_prune.to_vec(),
+        ]
+    }
+    /// The tables in the order of `to_bytes`. The data is used in place.
+    fn from_data(data: Vec<Data>) -> Result<Self, table::Error> {
+        let lens = [
+            2 * N_TWIST * N_MOVE,
+            2 * N_FLIP * N_MOVE,
+            2 * N_SLICE_SORTED * N_MOVE,
+            2 * N_PERM * N_MOVE,
+            2 * N_PERM * N_MOVE,
+            N_SLICE * N_TWIST,
+            N_SLICE * N_FLIP,
+            24 * N_PERM,
+            24 * N_PERM,
+        ];
+        if data.len() != lens.len() {
+            return Err(table::Error::Format);
+        }
+        for (d, &len) in data.iter().zip(&lens) {
+            table::check_len(d, len)?;
+        }
+        let mut data = data.into_iter();
+        let mut next = || data.next().unwrap();
+        Ok(Self {
+            twist_move: U16s::from_data(next()),
+            flip_move: U16s::from_data(next()),
+            slice_sorted_move: U16s::from_data(next()),
+            corners_move: U16s::from_data(next()),
+            ud_edges_move: U16s::from_data(next()),
+            slice_twist_prune: next(),
+            slice_flip_prune: next(),
+            corners_prune: next(),
+            ud_edges_prune: next(),
+        })
+    }
+    fn phase1_dist(&self, twist: usize, flip: usize, slice_sorted: usize) -> usize {
+        let s = slice_sorted / 24;
+        let a = self.slice_twist_prune[s * N_TWIST + twist];
+        let b = self.slice_flip_prune[s * N_FLIP + flip];
+        a.max(b) as usize
+    }
+    fn phase2_dist(&self, corners: usize, ud_edges: usize, slice_sorted: usize) -> usize {
+        let a = self.corners_prune[slice_sorted * N_PERM + corners];
+        let b = self.ud_edges_prune[slice_sorted * N_PERM + ud_edges];
+        a.max(b) as usize
+    }
+}
+static TABLES: OnceCell<Tables> = OnceCell::new();
+const TABLE_NAMES: [&str; 9] = [
+    "kociemba-twist-move",
+    "kociemba-flip-move",
+    "kociemba-slice-sorted-move",
+    "kociemba-corners-move",
+    "kociemba-ud-edges-move",
+    "kociemba-slice-twist-prune",
+    "kociemba-slice-flip-prune",
+    "kociemba-corners-prune",
+    "kociemba-ud-edges-prune",
+];
+
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(Tables::new)
+}
+
+/// Generate the tables in advance. Otherwise they are generated on the first solve.
+pub fn init() {
+    tables();
+}
+
+/// `init` with the tables saved in the directory.
+///
+/// The tables are generated and saved only if the files don't exist.
+/// Nothing is done if the tables are already generated.
+pub fn init_from(dir: &Path) -> Result<(), table::Error> {
+    if TABLES.get().is_some() {
+        return Ok(());
+    }
+    let data = table::load_or_build_all(dir, &TABLE_NAMES, || Tables::new().to_bytes())?;
+    let _ = TABLES.set(Tables::from_data(data)?);
+    Ok(())
+}
+
+struct Search<'a> {
+    t: &'a Tables,
+    cube: CubieCube,
+    ctl: &'a mut Control,
+    path: Vec<usize>,
+    /// Upper bound of the length of the next solution.
+    limit: usize,
+    best: Option<Vec<usize>>,
+    stop: bool,
+}
+impl<'a> Search<'a> {
+    fn timed_out(&mut self) -> bool {
+        if self.ctl.tick() {
+            self.stop = true;
+        }
+        self.stop
+    }
+    fn phase1(&mut self, twist: usize, flip: usize, slice_sorted: usize, togo: usize) {
+        if self.timed_out() {
+            return;
+        }
+        if togo == 0 {
+            // A phase 1 ending with a phase 2 move has been searched with shorter phase 1.
+            if self.path.last().map(|&m| is_phase2_move(m)) != Some(true) {
+                self.start_phase2();
+            }
+            return;
+        }
+        let t = self.t;
+        for m in 0..N_MOVE {
+            if !follows(self.path.last().copied(), m) {
+                continue;
+            }
+            let twist1 = t.twist_move.get(twist * N_MOVE + m) as usize;
+            let flip1 = t.flip_move.get(flip * N_MOVE + m) as usize;
+            let slice1 = t.slice_sorted_move.get(slice_sorted * N_MOVE + m) as usize;
+            let d = t.phase1_dist(twist1, flip1, slice1);
+            if d > togo - 1 || (d == 0 && togo > 1) {
+                continue;
+            }
+            self.path.push(m);
+            self.phase1(twist1, flip1, slice1, togo - 1);
+            self.path.pop();
+            if self.stop {
+                return;
+            }
+        }
+    }
+    /// Stops at the first solution within the limit.
+    fn start_phase2(&mut self) {
+        if self.path.len() > self.limit {
+            return;
+        }
+        let mut x = self.cube;
+        for &m in &self.path {
+            x = x.apply(m);
+        }
+        let (corners, ud_edges, slice_sorted) = (x.corners(), x.ud_edges(), x.slice_sorted());
+        let d = self.t.phase2_dist(corners, ud_edges, slice_sorted);
+        let n1 = self.path.len();
+        for togo in d..=self.limit - n1 {
+            if self.phase2(corners, ud_edges, slice_sorted, togo) {
+                let sol = self.path.clone();
+                self.path.truncate(n1);
+                self.best = Some(sol);
+                self.stop = true;
+                return;
+            }
+            if self.stop {
+                return;
+            }
+        }
+    }
+    fn phase2(
+        &mut self,
+        corners: usize,
+        ud_edges: usize,
+        slice_sorted: usize,
+        togo: usize,
+    ) -> bool {
+        if togo == 0 {
+            return corners == 0 && ud_edges == 0 && slice_sorted == 0;
+        }
+        if self.timed_out() {
+            return false;
+        }
+        let t = self.t;
+        for m in 0..N_MOVE {
+            if !is_phase2_move(m) || !follows(self.path.last().copied(), m) {
+                continue;
+            }
+            let corners1 = t.corners_move.get(corners * N_MOVE + m) as usize;
+            let ud_edges1 = t.ud_edges_move.get(ud_edges * N_MOVE + m) as usize;
+            let slice1 = t.slice_sorted_move.get(slice_sorted * N_MOVE + m) as usize;
+            if t.phase2_dist(corners1, ud_edges1, slice1) > togo - 1 {
+                continue;
+            }
+            self.path.push(m);
+            if self.phase2(corners1, ud_edges1, slice1, togo - 1) {
+                return true;
+            }
+            self.path.pop();
+        }
+        false
+    }
+}
+
+/// Shorter solutions one by one.
+///
+/// Each solution restarts the search with the tighter limit
+/// from the phase 1 depth where the previous solution was found.
+struct TwoPhaseStream {
+    cube: CubieCube,
+    max_length: usize,
+    ctl: Control,
+    depth: usize,
+    limit: usize,
+    done: bool,
+}
+impl TwoPhaseStream {
+    fn new(cube: &CubieCube, max_length: usize, ctl: Control) -> Self {
+        Self {
+            cube: *cube,
+            max_length,
+            ctl,
+            depth: 0,
+            limit: MAX_DEPTH,
+            done: !cube.is_solvable(),
+        }
+    }
+    fn next_path(&mut self) -> Option<Vec<usize>> {
+        if self.done || self.ctl.check() {
+            return None;
+        }
+        let t = tables();
+        let cube = self.cube;
+        let (twist, flip, slice_sorted) = (cube.twist(), cube.flip(), cube.slice_sorted());
+        let d = t.phase1_dist(twist, flip, slice_sorted);
+        let mut found = None;
+        for togo in self.depth.max(d)..=self.limit {
+            self.ctl.reach(togo);
+            let mut s = Search {
+                t,
+                cube,
+                ctl: &mut self.ctl,
+                path: vec![],
+                limit: self.limit,
+                best: None,
+                stop: false,
+            };
+            s.phase1(twist, flip, slice_sorted, togo);
+            if let Some(sol) = s.best {
+                self.depth = togo;
+                found = Some(sol);
+                break;
+            }
+            if s.stop {
+                break;
+            }
+        }
+        match found {
+            Some(sol) => {
+                if sol.len() <= self.max_length || sol.is_empty() {
+                    self.done = true;
+                } else {
+                    self.limit = sol.len() - 1;
+                }
+                Some(sol)
+            }
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+/// Solve the cube with face turns.
+///
+/// `None` if the state is broken or no solution is found in the time.
+pub fn solve_cubie(cube: &CubieCube, opts: &Options) -> Option<Vec<Command>> {
+    let deadline = Instant::now() + opts.timeout;
+    let ctl = Control::new(CancelToken::new(), Some(deadline));
+    let mut s = TwoPhaseStream::new(cube, opts.max_length, ctl);
+    let mut best = None;
+    while let Some(sol) = s.next_path() {
+        best = Some(sol);
+    }
+    best.map(|v| v.into_iter().map(cubie::command_of).collect())
+}
+
+/// Solve the state with face turns.
+///
+/// The state may include slice moves and rotations.
+/// The solution is solved up to the whole cube rotation.
+pub fn solve(m: &PermutationMatrix, opts: &Options) -> Option<Vec<Command>> {
+    let o = Oriented::new(m)?;
+    let sol = solve_cubie(&o.cube, opts)?;
+    Some(o.relabel(&sol))
+}
+
+struct OrientedStream {
+    o: Option<Oriented>,
+    s: TwoPhaseStream,
+}
+impl Stream for OrientedStream {
+    fn next_solution(&mut self) -> Option<Vec<Command>> {
+        let o = self.o.as_ref()?;
+        let sol: Vec<Command> = self
+            .s
+            .next_path()?
+            .into_iter()
+            .map(cubie::command_of)
+            .collect();
+        Some(o.relabel(&sol))
+    }
+    fn stats(&self) -> Stats {
+        self.s.ctl.stats()
+    }
+}
+/// Yields shorter solutions until one is within `max_length`.
+/// The deadline of the control is used instead of `timeout`.
+impl AnytimeSolver for Options {
+    fn start<'a>(&'a self, m: &PermutationMatrix, ctl: Control) -> Solutions<'a> {
+        let o = Oriented::new(m);
+        let cube = o
+            .as_ref()
+            .map(|o| o.cube)
+            .unwrap_or_else(CubieCube::identity);
+        Solutions::new(OrientedStream {
+            o,
+            s: TwoPhaseStream::new(&cube, self.max_length, ctl),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::solved;
+    use crate::matrix;
+
+    fn check(scramble: &[Command], opts: &Options) -> Vec<Command> {
+        let sol = solve(&matrix::of_seq(scramble), opts).unwrap();
+        let mut seq = scramble.to_vec();
+        seq.extend(&sol);
+        assert!(solved(&matrix::of_seq(&seq)));
+        sol
+    }
+    #[test]
+    fn test_solve() {
+        let opts = Options {
+            max_length: 25,
+            timeout: Duration::from_secs(30),
+        };
+        assert!(check(&[], &opts).is_empty());
+        for _ in 0..5 {
+            let sol = check(&crate::random(40), &opts);
+            assert!(sol.len() <= 25);
+        }
+    }
+    #[test]
+    fn test_default() {
+        init();
+        let opts = Options::default();
+        for _ in 0..3 {
+            let start = Instant::now();
+            let sol = check(&crate::random(40), &opts);
+            // The timeout is 1s. Generous for debug builds.
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert!(sol.len() <= 24);
+        }
+    }
+    #[test]
+    fn test_short_scramble() {
+        let opts = Options {
+            max_length: 6,
+            timeout: Duration::from_secs(30),
+        };
+        for _ in 0..5 {
+            let scramble: Vec<Command> = (0..6)
+                .map(|_| cubie::command_of(rand::random::<usize>() % N_MOVE))
+                .collect();
+            let sol = check(&scramble, &opts);
+            assert!(sol.len() <= 6);
+        }
+    }
+    #[test]
+    fn test_timeout() {
+        let opts = Options {
+            max_length: 0,
+            timeout: Duration::from_secs(3),
+        };
+        // The best solution so far is returned.
+        check(&crate::random(40), &opts);
+    }
+    #[test]
+    fn test_broken() {
+        let mut x = CubieCube::identity();
+        x.co[0] = 1;
+        assert_eq!(solve_cubie(&x, &Options::default()), None);
+    }
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("rubikmaster-kociemba-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = tables().to_bytes();
+        table::load_or_build_all(&dir, &TABLE_NAMES, || bytes.clone()).unwrap();
+        let data = table::load_or_build_all(&dir, &TABLE_NAMES, || unreachable!()).unwrap();
+        assert!(Tables::from_data(data.into_iter().skip(1).collect()).is_err());
+        let data = table::load_or_build_all(&dir, &TABLE_NAMES, || unreachable!()).unwrap();
+        assert_eq!(Tables::from_data(data).unwrap().to_bytes(), bytes);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+//! Solvers that find move sequences to solve a state.
+//!
+//! The solvers work on `CubieCube` and the tables are generated on the first use.
+//! The tables of `kociemba`, `thistlethwaite`, `zz`, `cross` and `optimal`
+//! can be saved and loaded by `init_from` or `load_or_new`.
+
+use crate::cubie::{CubieCube, N_MOVE};
+use crate::matrix::{self, PermutationMatrix};
+use crate::{Command, Move};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+pub mod anytime;
+pub mod beginner;
+pub mod block;
+pub mod cfop;
+pub mod cross;
+pub mod f2l;
+pub mod finder;
+pub mod kociemba;
+pub mod optimal;
+pub mod roux;
+pub mod table;
+pub mod thistlethwaite;
+pub mod zz;
+
+/// Check if the move can follow the previous move in a canonical sequence.
+///
+/// Moves on the same face should be merged and
+/// moves on the opposite faces are ordered like U D but not D U.
+pub(crate) fn follows(prev: Option<usize>, m: usize) -> bool {
+    match prev {
+        None => true,
+        Some(p) => {
+            let (a, b) = (p / 3, m / 3);
+            a != b && a != b + 3
+        }
+    }
+}
+
+/// The number of moves without rotations.
+pub(crate) fn move_count(cs: &[Command]) -> usize {
+    cs.iter()
+        .filter(|c| !matches!(c.0, Move::x | Move::y | Move::z))
+        .count()
+}
+
+/// `k` quarter turns of the move. `None` for no turn.
+pub(crate) fn turn(mov: Move, k: i8) -> Option<Command> {
+    match k.rem_euclid(4) {
+        0 => None,
+        3 => Some(Command(mov, -1)),
+        k => Some(Command(mov, k)),
+    }
+}
+/// The U turns of the AUF. `None` for no turn.
+pub(crate) fn auf_command(auf: i8) -> Option<Command> {
+    turn(Move::U, auf)
+}
+
+/// A step of the solution. The moves are empty if the step is skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub name: String,
+    pub moves: Vec<Command>,
+}
+impl Step {
+    /// The number of moves without rotations.
+    pub fn move_count(&self) -> usize {
+        move_count(&self.moves)
+    }
+}
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        for c in &self.moves {
+            write!(f, " {}", c)?;
+        }
+        write!(f, " ({})", self.move_count())
+    }
+}
+
+/// The steps of the solution in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    pub steps: Vec<Step>,
+}
+impl Solution {
+    /// All the moves of the steps.
+    pub fn moves(&self) -> Vec<Command> {
+        self.steps.iter().flat_map(|s| s.moves.clone()).collect()
+    }
+    /// The number of moves without rotations.
+    pub fn move_count(&self) -> usize {
+        self.steps.iter().map(|s| s.move_count()).sum()
+    }
+}
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for s in &self.steps {
+            writeln!(f, "{}", s)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state and the steps so far while solving step by step.
+pub(crate) struct Progress {
+    pub m: PermutationMatrix,
+    pub steps: Vec<Step>,
+}
+impl Progress {
+    pub fn new(m: &PermutationMatrix) -> Self {
+        Self {
+            m: *m,
+            steps: vec![],
+        }
+    }
+    /// Apply the moves and record them as a step.
+    pub fn push(&mut self, name: &str, moves: Vec<Command>) {
+        self.m = matrix::of_seq(&moves) * self.m;
+        self.steps.push(Step {
+            name: name.to_owned(),
+            moves,
+        });
+    }
+    pub fn finish(self) -> Solution {
+        Solution { steps: self.steps }
+    }
+}
+
+/// Map the tasks on the threads. The results are in the order of the tasks.
+pub(crate) fn par_map<T: Sync, R: Send>(
+    tasks: &[T],
+    threads: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    if threads <= 1 {
+        return tasks.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut out: Vec<(usize, R)> = thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut v = vec![];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= tasks.len() {
+                            return v;
+                        }
+                        v.push((i, f(&tasks[i])));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    });
+    out.sort_by_key(|x| x.0);
+    out.into_iter().map(|x| x.1).collect()
+}
+
+/// Distances from `start` computed by BFS over the coordinate.
+pub(crate) fn prune_table(
+    n: usize,
+    start: usize,
+    moves: &[usize],
+    next: impl Fn(usize, usize) -> usize,
+) -> Vec<u8> {
+    let mut dist = vec![u8::MAX; n];
+    let mut q = VecDeque::new();
+    dist[start] = 0;
+    q.push_back(start);
+    while let Some(x) = q.pop_front() {
+        let d = dist[x] + 1;
+        for &m in moves {
+            let y = next(x, m);
+            if dist[y] == u8::MAX {
+                dist[y] = d;
+                q.push_back(y);
+            }
+        }
+    }
+    dist
+}
+
+/// Transitions of a coordinate by the moves. Indexed by `coord * N_MOVE + move`.
+pub(crate) fn move_table(
+    n: usize,
+    moves: &[usize],
+    set: impl Fn(&mut CubieCube, usize),
+    get: impl Fn(&CubieCube) -> usize,
+) -> Vec<u16> {
+    let mut tbl = vec![0; n * N_MOVE];
+    for i in 0..n {
+        let mut x = CubieCube::identity();
+        set(&mut x, i);
+        for &m in moves {
+            tbl[i * N_MOVE + m] = get(&x.apply(m)) as u16;
+        }
+    }
+    tbl
+}
@@ -0,0 +1,658 @@
+//! Optimal solver in the half turn metric.
+//!
+//! IDA* over the face turns with Korf's pattern databases.
+//! A pattern database keeps the exact distance to solve a subset of the pieces
+//! and the maximum over the databases is an admissible heuristic.
+//!
+//! Solutions that differ only in the order of turns on opposite faces
+//! like U D and D U are reported once.
+
+use super::anytime::{AnytimeSolver, Control, Solutions, Stats, Stream};
+use super::table::{self, Data};
+use super::{follows, par_map};
+use crate::cubie::{self, CubieCube, Oriented, N_MOVE};
+use crate::matrix::PermutationMatrix;
+use crate::Command;
+use std::path::{Path, PathBuf};
+
+/// The depth of the first moves to split the work among the threads.
+const SPLIT_DEPTH: usize = 2;
+
+/// Subset of the pieces. The numbers are the pieces in the order of `cubie`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Corners(Vec<u8>),
+    Edges(Vec<u8>),
+}
+impl Pattern {
+    /// The number of positions and orientations of the kind of piece.
+    fn shape(&self) -> (usize, usize) {
+        match self {
+            Pattern::Corners(_) => (8, 3),
+            Pattern::Edges(_) => (12, 2),
+        }
+    }
+    fn pieces(&self) -> &[u8] {
+        match self {
+            Pattern::Corners(v) => v,
+            Pattern::Edges(v) => v,
+        }
+    }
+}
+
+/// Positions and orientations of the pieces in a pattern.
+#[derive(Clone, Copy)]
+struct Partial {
+    pos: [u8; 12],
+    ori: [u8; 12],
+}
+
+/// Exact distances to solve the pieces in a pattern.
+pub struct PatternDb {
+    pattern: Pattern,
+    n: usize,
+    o: usize,
+    /// The piece at the position `j` moves to `dest[m][j]` by the move `m`.
+    dest: Vec<[u8; 12]>,
+    /// Orientation added to the piece at the position `j` by the move `m`.
+    twist: Vec<[u8; 12]>,
+    dist: Data,
+}
+impl PatternDb {
+    /// The database without the distances.
+    fn empty(pattern: Pattern) -> Self {
+        let (n, o) = pattern.shape();
+        let k = pattern.pieces().len();
+        assert!(k > 0 && k <= n);
+        let mut dest = vec![[0; 12]; N_MOVE];
+        let mut twist = vec![[0; 12]; N_MOVE];
+        for m in 0..N_MOVE {
+            let mv = CubieCube::identity().apply(m);
+            for i in 0..n {
+                let (j, t) = match pattern {
+                    Pattern::Corners(_) => (mv.cp[i], mv.co[i]),
+                    Pattern::Edges(_) => (mv.ep[i], mv.eo[i]),
+                };
+                dest[m][j as usize] = i as u8;
+                twist[m][j as usize] = t;
+            }
+        }
+        Self {
+            pattern,
+            n,
+            o,
+            dest,
+            twist,
+            dist: Data::Owned(vec![]),
+        }
+    }
+    /// Distances by BFS from the solved state.
+    fn build(&self) -> Vec<u8> {
+        let mut dist = vec![u8::MAX; self.len()];
+        let solved = self.index_of(&CubieCube::identity());
+        dist[solved] = 0;
+        let mut depth = 0;
+        loop {
+            let mut found = false;
+            for idx in 0..dist.len() {
+                if dist[idx] != depth {
+                    continue;
+                }
+                let x = self.decode(idx);
+                for m in 0..N_MOVE {
+                    let y = self.encode(&self.apply(&x, m));
+                    if dist[y] == u8::MAX {
+                        dist[y] = depth + 1;
+                        found = true;
+                    }
+                }
+            }
+            if !found {
+                break;
+            }
+            depth += 1;
+        }
+        dist
+    }
+    /// Generate the database by BFS from the solved state.
+    pub fn new(pattern: Pattern) -> Self {
+        let mut db = Self::empty(pattern);
+        db.dist = Data::Owned(db.build());
+        db
+    }
+    /// The name of the table like `corners-0-1-2-3`.
+    pub fn name(&self) -> String {
+        let kind = match self.pattern {
+            Pattern::Corners(_) => "corners",
+            Pattern::Edges(_) => "edges",
+        };
+        let pieces: Vec<String> = self
+            .pattern
+            .pieces()
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        format!("{}-{}", kind, pieces.join("-"))
+    }
+    /// The file of the database in the directory like `corners-0-1-2-3.bin`.
+    pub(crate) fn path_in(pattern: &Pattern, dir: &Path) -> PathBuf {
+        let name = PatternDb::empty(pattern.clone()).name();
+        dir.join(format!("{}.bin", name))
+    }
+    /// Save the distances to the file.
+    pub fn save(&self, path: &Path) -> Result<(), table::Error> {
+        table::save(path, &self.name(), &self.dist)
+    }
+    /// Load the database saved by `save`.
+    pub fn load(pattern: Pattern, path: &Path) -> Result<Self, table::Error> {
+        let mut db = Self::empty(pattern);
+        db.dist = table::load(path, &db.name())?;
+        db.check_len()?;
+        Ok(db)
+    }
+    /// Load the database or generate and save it if the file doesn't exist.
+    pub fn load_or_new(pattern: Pattern, path: &Path) -> Result<Self, table::Error> {
+        let mut db = Self::empty(pattern);
+        db.dist = table::load_or_build(path, &db.name(), || db.build())?;
+        db.check_len()?;
+        Ok(db)
+    }
+    fn check_len(&self) -> Result<(), table::Error> {
+        if self.dist.len() != self.len() {
+            return Err(table::Error::Format);
+        }
+        Ok(())
+    }
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+    fn k(&self) -> usize {
+        self.pattern.pieces().len()
+    }
+    /// The orientation of the last piece is determined by the others if all pieces are in the pattern.
+    fn n_ori(&self) -> usize {
+        if self.k() == self.n {
+            self.k() - 1
+        } else {
+            self.k()
+        }
+    }
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        let mut x = 1;
+        for t in 0..self.k() {
+            x *= self.n - t;
+        }
+        x * self.o.pow(self.n_ori() as u32)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The maximum distance in the database.
+    pub fn max_distance(&self) -> usize {
+        self.dist.iter().copied().max().unwrap_or(0) as usize
+    }
+    fn apply(&self, x: &Partial, m: usize) -> Partial {
+        let mut y = *x;
+        for t in 0..self.k() {
+            let j = x.pos[t] as usize;
+            y.pos[t] = self.dest[m][j];
+            y.ori[t] = (x.ori[t] + self.twist[m][j]) % self.o as u8;
+        }
+        y
+    }
+    fn encode(&self, x: &Partial) -> usize {
+        let mut idx = 0;
+        let mut used = 0u32;
+        for t in 0..self.k() {
+            let p = x.pos[t] as u32;
+            let r = p - (used & ((1 << p) - 1)).count_ones();
+            idx = idx * (self.n - t) + r as usize;
+            used |= 1 << p;
+        }
+        for t in 0..self.n_ori() {
+            idx = idx * self.o + x.ori[t] as usize;
+        }
+        idx
+    }
+    fn decode(&self, mut idx: usize) -> Partial {
+        let mut x = Partial {
+            pos: [0; 12],
+            ori: [0; 12],
+        };
+        let k = self.k();
+        let mut sum = 0;
+        for t in (0..self.n_ori()).rev() {
+            x.ori[t] = (idx % self.o) as u8;
+            sum += x.ori[t] as usize;
+            idx /= self.o;
+        }
+        if self.n_ori() < k {
+            x.ori[k - 1] = ((self.o - sum % self.o) % self.o) as u8;
+        }
+        let mut rank = [0; 12];
+        for t in (0..k).rev() {
+            rank[t] = idx % (self.n - t);
+            idx /= self.n - t;
+        }
+        let mut used = [false; 12];
+        for (t, &r) in rank[..k].iter().enumerate() {
+            let p = (0..self.n).filter(|&p| !used[p]).nth(r).unwrap();
+            used[p] = true;
+            x.pos[t] = p as u8;
+        }
+        x
+    }
+    fn index_of(&self, cube: &CubieCube) -> usize {
+        let mut x = Partial {
+            pos: [0; 12],
+            ori: [0; 12],
+        };
+        let (perm, ori): (&[u8], &[u8]) = match self.pattern {
+            Pattern::Corners(_) => (&cube.cp, &cube.co),
+            Pattern::Edges(_) => (&cube.ep, &cube.eo),
+        };
+        for (t, &piece) in self.pattern.pieces().iter().enumerate() {
+            let i = perm.iter().position(|&p| p == piece).unwrap();
+            x.pos[t] = i as u8;
+            x.ori[t] = ori[i];
+        }
+        self.encode(&x)
+    }
+    /// The number of moves to solve the pieces in the pattern.
+    pub fn distance(&self, cube: &CubieCube) -> usize {
+        self.dist[self.index_of(cube)] as usize
+    }
+}
+
+pub struct Solver {
+    dbs: Vec<PatternDb>,
+}
+impl Solver {
+    /// Generate the pattern databases.
+    pub fn new(patterns: Vec<Pattern>) -> Self {
+        let dbs = patterns.into_iter().map(PatternDb::new).collect();
+        Self { dbs }
+    }
+    /// Korf's databases: all corners and two sets of six edges.
+    ///
+    /// The generation takes a few minutes and about 170MB of memory.
+    pub fn korf() -> Self {
+        Self::new(vec![
+            Pattern::Corners((0..8).collect()),
+            Pattern::Edges((0..6).collect()),
+            Pattern::Edges((6..12).collect()),
+        ])
+    }
+    /// Small databases that are generated quickly.
+    /// The search is slower for long scrambles.
+    pub fn small() -> Self {
+        Self::new(vec![
+            Pattern::Corners(vec![0, 1, 2, 3]),
+            Pattern::Corners(vec![4, 5, 6, 7]),
+            Pattern::Edges(vec![0, 1, 2, 3]),
+            Pattern::Edges(vec![4, 5, 6, 7]),
+            Pattern::Edges(vec![8, 9, 10, 11]),
+        ])
+    }
+    /// `new` with the databases saved in the directory.
+    ///
+    /// The databases are generated and saved only if the files don't exist.
+    pub fn load_or_new(patterns: Vec<Pattern>, dir: &Path) -> Result<Self, table::Error> {
+        let mut dbs = vec![];
+        for pattern in patterns {
+            let path = PatternDb::path_in(&pattern, dir);
+            dbs.push(PatternDb::load_or_new(pattern, &path)?);
+        }
+        Ok(Self { dbs })
+    }
+    pub fn databases(&self) -> &[PatternDb] {
+        &self.dbs
+    }
+    /// Lower bound of the distance.
+    pub fn heuristic(&self, cube: &CubieCube) -> usize {
+        self.dbs
+            .iter()
+            .map(|db| db.distance(cube))
+            .max()
+            .unwrap_or(0)
+    }
+    fn search(
+        &self,
+        cube: &CubieCube,
+        togo: usize,
+        path: &mut Vec<usize>,
+        all: bool,
+        ctl: &mut Control,
+        out: &mut Vec<Vec<usize>>,
+    ) {
+        if ctl.tick() {
+            return;
+        }
+        if togo == 0 {
+            if cube.is_solved() {
+                out.push(path.clone());
+            }
+            return;
+        }
+        for m in 0..N_MOVE {
+            if !follows(path.last().copied(), m) {
+                continue;
+            }
+            let next = cube.apply(m);
+            if self.heuristic(&next) > togo - 1 {
+                continue;
+            }
+            path.push(m);
+            self.search(&next, togo - 1, path, all, ctl, out);
+            path.pop();
+            if (!all && !out.is_empty()) || ctl.stopped() {
+                return;
+            }
+        }
+    }
+    /// `Some(vec![])` if stopped by the control.
+    fn solve_cubie(
+        &self,
+        cube: &CubieCube,
+        all: bool,
+        ctl: &mut Control,
+    ) -> Option<Vec<Vec<Command>>> {
+        if !cube.is_solvable() {
+            return None;
+        }
+        let mut out = vec![];
+        let mut depth = self.heuristic(cube);
+        while !ctl.check() {
+            ctl.reach(depth);
+            self.search(cube, depth, &mut vec![], all, ctl, &mut out);
+            if !out.is_empty() {
+                break;
+            }
+            depth += 1;
+        }
+        let v = out
+            .into_iter()
+            .map(|p| p.into_iter().map(cubie::command_of).collect())
+            .collect();
+        Some(v)
+    }
+    /// The canonical sequences of the length within the heuristic.
+    fn prefixes(
+        &self,
+        cube: &CubieCube,
+        depth: usize,
+        togo: usize,
+        path: &mut Vec<usize>,
+        out: &mut Vec<(Vec<usize>, CubieCube)>,
+    ) {
+        if togo == 0 {
+            out.push((path.clone(), *cube));
+            return;
+        }
+        for m in 0..N_MOVE {
+            if !follows(path.last().copied(), m) {
+                continue;
+            }
+            let next = cube.apply(m);
+            if self.heuristic(&next) > depth - path.len() - 1 {
+                continue;
+            }
+            path.push(m);
+            self.prefixes(&next, depth, togo - 1, path, out);
+            path.pop();
+        }
+    }
+    /// `solve_cubie` with all the solutions by the threads.
+    fn solve_cubie_parallel(&self, cube: &CubieCube, threads: usize) -> Option<Vec<Vec<Command>>> {
+        if !cube.is_solvable() {
+            return None;
+        }
+        let mut depth = self.heuristic(cube);
+        let out = loop {
+            let mut tasks = vec![];
+            self.prefixes(cube, depth, depth.min(SPLIT_DEPTH), &mut vec![], &mut tasks);
+            let found = par_map(&tasks, threads, |(path, x)| {
+                let mut out = vec![];
+                let togo = depth - path.len();
+                let mut path = path.clone();
+                self.search(x, togo, &mut path, true, &mut Control::default(), &mut out);
+                out
+            });
+            let out: Vec<Vec<usize>> = found.into_iter().flatten().collect();
+            if !out.is_empty() {
+                break out;
+            }
+            depth += 1;
+        };
+        let v = out
+            .into_iter()
+            .map(|p| p.into_iter().map(cubie::command_of).collect())
+            .collect();
+        Some(v)
+    }
+    /// Find an optimal solution.
+    ///
+    /// `None` if the state is broken.
+    /// The solution is solved up to the whole cube rotation.
+    pub fn solve(&self, m: &PermutationMatrix) -> Option<Vec<Command>> {
+        let o = Oriented::new(m)?;
+        let mut v = self.solve_cubie(&o.cube, false, &mut Control::default())?;
+        Some(o.relabel(&v.remove(0)))
+    }
+    /// Find all optimal solutions.
+    pub fn solve_all(&self, m: &PermutationMatrix) -> Option<Vec<Vec<Command>>> {
+        let o = Oriented::new(m)?;
+        let v = self.solve_cubie(&o.cube, true, &mut Control::default())?;
+        Some(v.iter().map(|s| o.relabel(s)).collect())
+    }
+    /// `solve_all` by the threads. The order of the solutions is the same.
+    pub fn solve_all_parallel(
+        &self,
+        m: &PermutationMatrix,
+        threads: usize,
+    ) -> Option<Vec<Vec<Command>>> {
+        let o = Oriented::new(m)?;
+        let v = self.solve_cubie_parallel(&o.cube, threads)?;
+        Some(v.iter().map(|s| o.relabel(s)).collect())
+    }
+    /// The length of the optimal solutions.
+    ///
+    /// As expensive as `solve` because the search has to reach a solution.
+    pub fn distance(&self, m: &PermutationMatrix) -> Option<usize> {
+        self.solve(m).map(|s| s.len())
+    }
+}
+
+struct OptimalStream<'a> {
+    solver: &'a Solver,
+    m: PermutationMatrix,
+    ctl: Control,
+    done: bool,
+}
+impl Stream for OptimalStream<'_> {
+    fn next_solution(&mut self) -> Option<Vec<Command>> {
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        let o = Oriented::new(&self.m)?;
+        let mut v = self.solver.solve_cubie(&o.cube, false, &mut self.ctl)?;
+        if v.is_empty() {
+            return None;
+        }
+        Some(o.relabel(&v.remove(0)))
+    }
+    fn stats(&self) -> Stats {
+        self.ctl.stats()
+    }
+}
+/// Yields the optimal solution only.
+impl AnytimeSolver for Solver {
+    fn start<'a>(&'a self, m: &PermutationMatrix, ctl: Control) -> Solutions<'a> {
+        Solutions::new(OptimalStream {
+            solver: self,
+            m: *m,
+            ctl,
+            done: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::solved;
+    use crate::matrix;
+    use crate::parser::parse;
+    use crate::{flatten, Move};
+    use once_cell::sync::Lazy;
+    use std::collections::HashMap;
+
+    static SOLVER: Lazy<Solver> = Lazy::new(Solver::small);
+
+    fn random_face_turns(n: usize) -> Vec<Command> {
+        (0..n)
+            .map(|_| cubie::command_of(rand::random::<usize>() % N_MOVE))
+            .collect()
+    }
+    #[test]
+    fn test_encode() {
+        for db in SOLVER.databases() {
+            for _ in 0..100 {
+                let idx = rand::random::<usize>() % db.len();
+                assert_eq!(db.encode(&db.decode(idx)), idx);
+            }
+            assert!(db.dist.iter().all(|&d| d != u8::MAX));
+        }
+        assert_eq!(SOLVER.databases()[0].len(), 8 * 7 * 6 * 5 * 81);
+    }
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("rubikmaster-db-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let patterns = vec![
+            Pattern::Corners(vec![0, 1, 2, 3]),
+            Pattern::Edges(vec![0, 1, 2]),
+        ];
+        let a = Solver::load_or_new(patterns.clone(), &dir).unwrap();
+        let b = Solver::load_or_new(patterns, &dir).unwrap();
+        for (x, y) in a.databases().iter().zip(b.databases()) {
+            assert_eq!(&*x.dist, &*y.dist);
+        }
+        let path = dir.join("corners-0-1-2-3.bin");
+        let db = PatternDb::load(Pattern::Corners(vec![0, 1, 2, 3]), &path).unwrap();
+        assert_eq!(&*db.dist, &*a.databases()[0].dist);
+        assert!(matches!(
+            PatternDb::load(Pattern::Corners(vec![4, 5, 6, 7]), &path),
+            Err(table::Error::Name(_))
+        ));
+        let m = matrix::of_seq(&random_face_turns(4));
+        assert_eq!(b.distance(&m), SOLVER.distance(&m));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_distance() {
+        // Exact distances up to 3 moves.
+        let mut dist = HashMap::new();
+        let mut frontier = vec![CubieCube::identity()];
+        dist.insert(CubieCube::identity(), 0);
+        for d in 1..=3 {
+            let mut next = vec![];
+            for x in frontier {
+                for m in 0..N_MOVE {
+                    let y = x.apply(m);
+                    if let std::collections::hash_map::Entry::Vacant(e) = dist.entry(y) {
+                        e.insert(d);
+                        next.push(y);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        for _ in 0..20 {
+            let cs = random_face_turns(3);
+            let m = matrix::of_seq(&cs);
+            let x = CubieCube::from_matrix(&m).unwrap();
+            assert!(SOLVER.heuristic(&x) <= dist[&x]);
+            assert_eq!(SOLVER.distance(&m), Some(dist[&x]));
+        }
+    }
+    /// Takes minutes even in release builds: `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_korf() {
+        let korf = Solver::korf();
+        let lens: Vec<usize> = korf.databases().iter().map(|db| db.len()).collect();
+        assert_eq!(lens, [40320 * 2187, 665280 * 64, 665280 * 64]);
+        for _ in 0..3 {
+            let cs = random_face_turns(12);
+            let m = matrix::of_seq(&cs);
+            let sol = korf.solve(&m).unwrap();
+            assert!(sol.len() <= 12);
+            assert!(solved(&(matrix::of_seq(&sol) * m)));
+            let x = CubieCube::from_matrix(&m).unwrap();
+            assert!(korf.heuristic(&x) <= sol.len());
+        }
+    }
+    #[test]
+    fn test_solve() {
+        for _ in 0..5 {
+            let scramble = random_face_turns(6);
+            let m = matrix::of_seq(&scramble);
+            let sol = SOLVER.solve(&m).unwrap();
+            assert!(sol.len() <= 6);
+            let mut seq = scramble.clone();
+            seq.extend(&sol);
+            assert!(solved(&matrix::of_seq(&seq)));
+        }
+        let sexy = flatten(parse("RUR'U'").unwrap().1);
+        assert_eq!(SOLVER.distance(&matrix::of_seq(&sexy)), Some(4));
+        // Slice moves and rotations in the scramble.
+        let cs = flatten(parse("M2 x U r'").unwrap().1);
+        let sol = SOLVER.solve(&matrix::of_seq(&cs)).unwrap();
+        assert_eq!(sol.len(), 4);
+        let mut seq = cs.clone();
+        seq.extend(&sol);
+        assert!(solved(&matrix::of_seq(&seq)));
+    }
+    #[test]
+    fn test_parallel() {
+        for seed in 0..3 {
+            let m = matrix::of_seq(&crate::random_seeded(8, seed));
+            let v = SOLVER.solve_all(&m).unwrap();
+            // The solutions go deeper than the split.
+            assert!(v[0].len() > SPLIT_DEPTH + 2);
+            assert_eq!(SOLVER.solve_all_parallel(&m, 4), Some(v));
+        }
+        assert_eq!(
+            SOLVER.solve_all_parallel(&PermutationMatrix::identity(), 4),
+            Some(vec![vec![]])
+        );
+    }
+    #[test]
+    fn test_solve_all() {
+        let cs = flatten(parse("RL").unwrap().1);
+        let v = SOLVER.solve_all(&matrix::of_seq(&cs)).unwrap();
+        assert_eq!(v, vec![vec![Command(Move::R, -1), Command(Move::L, -1)]]);
+
+        // Self-inverse with several optimal solutions
+        let cs = flatten(parse("R2 U2 R2 U2 R2 U2").unwrap().1);
+        let v = SOLVER.solve_all(&matrix::of_seq(&cs)).unwrap();
+        assert!(v.contains(&cs));
+        assert!(v.contains(&flatten(parse("U2 R2 U2 R2 U2 R2").unwrap().1)));
+        for i in 0..v.len() {
+            assert!(!v[i + 1..].contains(&v[i]));
+        }
+        for s in v {
+            assert_eq!(s.len(), 6);
+            let mut seq = cs.clone();
+            seq.extend(&s);
+            assert!(solved(&matrix::of_seq(&seq)));
+        }
+        assert_eq!(
+            SOLVER.solve_all(&PermutationMatrix::identity()),
+            Some(vec![vec![]])
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Roux solver.
+//!
+//! The blocks are searched by IDA* over the positions of the pieces of the block.
+//! The second block only uses R, r, U and M so the first block is kept.
+//! The CMLL uses `roux::CMLL_LIST` and the last six edges are searched in <M, U>.
+
+use super::block::{block, BlockSearch};
+use super::{Progress, Solution};
+use crate::cfop;
+use crate::cubie;
+use crate::matrix::{self, PermutationMatrix};
+use crate::roux::{self, recognize_cmll, CMLL_LIST};
+use crate::{flatten, parser, Command, Move};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+
+/// No block needs more moves.
+const MAX_DEPTH: usize = 20;
+
+static FIRST_BLOCK: Lazy<BlockSearch> = Lazy::new(|| {
+    let faces = [
+        Move::U,
+        Move::D,
+        Move::F,
+        Move::B,
+        Move::R,
+        Move::L,
+        Move::M,
+        Move::r,
+    ];
+    block([5, 6], [6, 9, 10], &faces)
+});
+static SECOND_BLOCK: Lazy<BlockSearch> =
+    Lazy::new(|| block([4, 7], [4, 8, 11], &[Move::R, Move::r, Move::U, Move::M]));
+
+/// The moves of the last six edges with their matrices.
+static LSE_MOVES: Lazy<Vec<(Command, PermutationMatrix)>> = Lazy::new(|| {
+    [Move::M, Move::U]
+        .iter()
+        .flat_map(|&face| [1, 2, -1].map(|k| Command(face, k)))
+        .map(|c| (c, matrix::of_seq(&[c])))
+        .collect()
+});
+
+/// The shortest sequence of <M, U> to reach the goal.
+/// `None` if the goal can't be reached.
+fn lse(m: &PermutationMatrix, goal: impl Fn(&PermutationMatrix) -> bool) -> Option<Vec<Command>> {
+    let mut parent: HashMap<PermutationMatrix, (PermutationMatrix, Command)> = HashMap::new();
+    let mut q = VecDeque::new();
+    q.push_back(*m);
+    let mut x = loop {
+        let y = q.pop_front()?;
+        if goal(&y) {
+            break y;
+        }
+        for &(c, mat) in LSE_MOVES.iter() {
+            let z = mat * y;
+            if z != *m && !parent.contains_key(&z) {
+                parent.insert(z, (y, c));
+                q.push_back(z);
+            }
+        }
+    };
+    let mut path = vec![];
+    while let Some(&(p, c)) = parent.get(&x) {
+        path.push(c);
+        x = p;
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Solve the cube by Roux.
+///
+/// The whole cube is rotated first to bring the centers home.
+/// `None` if the state is broken.
+pub fn solve(m: &PermutationMatrix) -> Option<Solution> {
+    let o = cubie::Oriented::new(m)?;
+    if !o.cube.is_solvable() {
+        return None;
+    }
+    let rot = cubie::rotations()
+        .iter()
+        .find(|r| {
+            let x = matrix::of_seq(r) * *m;
+            cubie::center_facelets()
+                .iter()
+                .all(|&c| x.inv_perm[c as usize] == c)
+        })
+        .unwrap();
+    let mut s = Progress::new(m);
+
+    let mut moves = rot.clone();
+    moves.extend(FIRST_BLOCK.solve(&(matrix::of_seq(rot) * *m), MAX_DEPTH)?);
+    s.push("First block", moves);
+    let moves = SECOND_BLOCK.solve(&s.m, MAX_DEPTH)?;
+    s.push("Second block", moves);
+
+    let moves = match recognize_cmll(&s.m) {
+        Some((name, pre, post)) => {
+            let alg = CMLL_LIST.iter().find(|x| x.0 == name).unwrap().1;
+            let mut cs: Vec<Command> = super::auf_command(pre).into_iter().collect();
+            cs.extend(flatten(parser::parse(alg).unwrap().1));
+            cs.extend(super::auf_command(post));
+            cs
+        }
+        // Only the AUF is left.
+        None => {
+            let k = (0..4)
+                .find(|&k| roux::cmll_solved(&(matrix::of_seq(&[Command(Move::U, k)]) * s.m)))?;
+            super::auf_command(k).into_iter().collect()
+        }
+    };
+    s.push("CMLL", moves);
+
+    let moves = lse(&s.m, roux::lse_eo_solved)?;
+    s.push("LSE EO", moves);
+    let moves = lse(&s.m, |x| roux::lse_eo_solved(x) && roux::ulur_solved(x))?;
+    s.push("LSE UL/UR", moves);
+    let moves = lse(&s.m, cfop::solved)?;
+    s.push("LSE 4c", moves);
+    Some(s.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roux::{blocks_solved, cmll_solved, first_block_solved};
+
+    #[test]
+    fn test_block_search() {
+        let cs = flatten(parser::parse("rUM'F").unwrap().1);
+        let m = matrix::of_seq(&cs);
+        let sol = FIRST_BLOCK.solve(&m, MAX_DEPTH).unwrap();
+        assert!(sol.len() <= 4);
+        assert!(first_block_solved(&(matrix::of_seq(&sol) * m)));
+        // The first block is kept.
+        let cs = flatten(parser::parse("RUMr'U2M").unwrap().1);
+        let m = matrix::of_seq(&cs);
+        let sol = SECOND_BLOCK.solve(&m, MAX_DEPTH).unwrap();
+        assert!(sol.len() <= 6);
+        assert!(blocks_solved(&(matrix::of_seq(&sol) * m)));
+    }
+    #[test]
+    fn test_lse() {
+        let m = matrix::of_seq(&flatten(parser::parse("MU2M'").unwrap().1));
+        let sol = lse(&m, cfop::solved).unwrap();
+        assert_eq!(sol.len(), 3);
+        assert!(cfop::solved(&(matrix::of_seq(&sol) * m)));
+        // R can't be undone in <M, U>.
+        let m = matrix::of_seq(&[Command(Move::R, 1)]);
+        assert_eq!(lse(&m, cfop::solved), None);
+    }
+    #[test]
+    fn test_broken() {
+        let mut x = cubie::CubieCube::identity();
+        x.co[0] = 1;
+        assert!(solve(&x.to_matrix()).is_none());
+    }
+    #[test]
+    fn test_solve() {
+        for _ in 0..30 {
+            let m = matrix::of_seq(&crate::random(25));
+            let sol = solve(&m).unwrap();
+            let names: Vec<&str> = sol.steps.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(
+                names,
+                [
+                    "First block",
+                    "Second block",
+                    "CMLL",
+                    "LSE EO",
+                    "LSE UL/UR",
+                    "LSE 4c"
+                ]
+            );
+            let mut x = m;
+            for (i, step) in sol.steps.iter().enumerate() {
+                x = matrix::of_seq(&step.moves) * x;
+                match i {
+                    0 => assert!(first_block_solved(&x)),
+                    1 => assert!(blocks_solved(&x)),
+                    2 => assert!(cmll_solved(&x)),
+                    _ => assert!(blocks_solved(&x)),
+                }
+            }
+            assert!(cfop::solved(&x));
+            // The second block and LSE are done with R, r, U and M.
+            for i in [1, 3, 4, 5] {
+                let ok = [Move::R, Move::r, Move::U, Move::M];
+                assert!(sol.steps[i].moves.iter().all(|c| ok.contains(&c.0)));
+            }
+        }
+    }
+}
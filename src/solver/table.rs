@@ -0,0 +1,329 @@
+//! Persistent pruning tables.
+//!
+//! A table is saved with a header so a stale or broken file is detected on load.
+//! The layout in little endian is
+//!
+//! - magic `RMPT`
+//! - format version: u32
+//! - fingerprint of the moves in `matrix::of`: u64
+//! - length of the data: u64
+//! - FNV-1a checksum of the data: u64
+//! - length of the name: u32 and the name padded to 8 bytes
+//! - the data
+//!
+//! The data starts at an aligned offset so it can be used in place.
+//! With the `mmap` feature the file is memory-mapped on load.
+
+use crate::matrix;
+use crate::{Command, MOVE_LIST};
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"RMPT";
+/// Bumped when the layout changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Not a table file or truncated.
+    Format,
+    /// Written by another format version.
+    Version(u32),
+    /// Built with different move definitions.
+    Moves {
+        expected: u64,
+        found: u64,
+    },
+    /// The data is broken.
+    Checksum,
+    /// Another table. The name in the file.
+    Name(String),
+}
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Format => write!(f, "not a table file"),
+            Error::Version(v) => write!(f, "format version {} is not supported", v),
+            Error::Moves { expected, found } => write!(
+                f,
+                "built with other move definitions ({:016x} != {:016x})",
+                found, expected
+            ),
+            Error::Checksum => write!(f, "checksum mismatch"),
+            Error::Name(name) => write!(f, "another table {}", name),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+/// Hash of the permutations of all the moves.
+/// Tables built with other definitions of the moves are rejected.
+pub fn moves_fingerprint() -> u64 {
+    let mut bytes = vec![];
+    for &mov in &MOVE_LIST {
+        bytes.extend(matrix::of_seq(&[Command(mov, 1)]).inv_perm);
+    }
+    fnv1a(&bytes)
+}
+
+fn padded(n: usize) -> usize {
+    n.div_ceil(8) * 8
+}
+
+/// Serialize the table.
+pub fn encode(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut v = MAGIC.to_vec();
+    v.extend(FORMAT_VERSION.to_le_bytes());
+    v.extend(moves_fingerprint().to_le_bytes());
+    v.extend((data.len() as u64).to_le_bytes());
+    v.extend(fnv1a(data).to_le_bytes());
+    v.extend((name.len() as u32).to_le_bytes());
+    v.extend(name.as_bytes());
+    v.resize(padded(v.len()), 0);
+    v.extend(data);
+    v
+}
+
+/// The data in the serialized table without copying.
+pub fn decode<'a>(bytes: &'a [u8], name: &str) -> Result<&'a [u8], Error> {
+    let start = data_start(bytes, name)?;
+    Ok(&bytes[start..])
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap())
+}
+fn u64_at(bytes: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap())
+}
+fn checksum(bytes: &[u8]) -> u64 {
+    u64_at(bytes, 24)
+}
+/// The range of the data.
+fn header(bytes: &[u8], name: &str) -> Result<(usize, usize), Error> {
+    if bytes.len() < 36 || &bytes[..4] != MAGIC {
+        return Err(Error::Format);
+    }
+    let version = u32_at(bytes, 4);
+    if version != FORMAT_VERSION {
+        return Err(Error::Version(version));
+    }
+    let (expected, found) = (moves_fingerprint(), u64_at(bytes, 8));
+    if found != expected {
+        return Err(Error::Moves { expected, found });
+    }
+    let len = u64_at(bytes, 16) as usize;
+    let name_len = u32_at(bytes, 32) as usize;
+    let start = padded(36 + name_len);
+    if bytes.len() < start || bytes.len() - start != len {
+        return Err(Error::Format);
+    }
+    let found = String::from_utf8_lossy(&bytes[36..36 + name_len]);
+    if found != name {
+        return Err(Error::Name(found.into_owned()));
+    }
+    Ok((start, len))
+}
+/// Check the header and the checksum.
+fn data_start(bytes: &[u8], name: &str) -> Result<usize, Error> {
+    let (start, len) = header(bytes, name)?;
+    if fnv1a(&bytes[start..start + len]) != checksum(bytes) {
+        return Err(Error::Checksum);
+    }
+    Ok(start)
+}
+
+/// Write the table to the file.
+pub fn save(path: &Path, name: &str, data: &[u8]) -> Result<(), Error> {
+    fs::write(path, encode(name, data))?;
+    Ok(())
+}
+
+/// The data of a loaded table.
+pub enum Data {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "mmap")))]
+    Mapped(memmap2::Mmap, usize),
+}
+impl Deref for Data {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self {
+            Data::Owned(v) => v,
+            #[cfg(feature = "mmap")]
+            Data::Mapped(m, start) => &m[*start..],
+        }
+    }
+}
+
+/// Read the table from the file. The file is memory-mapped with the `mmap` feature.
+pub fn load(path: &Path, name: &str) -> Result<Data, Error> {
+    #[cfg(feature = "mmap")]
+    {
+        let file = fs::File::open(path)?;
+        // Safety: The file is not expected to be modified while mapped.
+        let m = unsafe { memmap2::Mmap::map(&file)? };
+        let start = data_start(&m, name)?;
+        Ok(Data::Mapped(m, start))
+    }
+    #[cfg(not(feature = "mmap"))]
+    {
+        let mut bytes = fs::read(path)?;
+        let start = data_start(&bytes, name)?;
+        bytes.drain(..start);
+        Ok(Data::Owned(bytes))
+    }
+}
+
+/// Load the table or build and save it if the file doesn't exist.
+/// Other errors like the version mismatch are returned.
+pub fn load_or_build(
+    path: &Path,
+    name: &str,
+    build: impl FnOnce() -> Vec<u8>,
+) -> Result<Data, Error> {
+    match load(path, name) {
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+            let data = build();
+            save(path, name, &data)?;
+            Ok(Data::Owned(data))
+        }
+        x => x,
+    }
+}
+
+/// Load the tables saved in the directory as `<name>.bin`
+/// or build all of them and save them if any of the files doesn't exist.
+///
+/// For solvers whose tables are generated together.
+pub(crate) fn load_or_build_all(
+    dir: &Path,
+    names: &[&str],
+    build: impl FnOnce() -> Vec<Vec<u8>>,
+) -> Result<Vec<Data>, Error> {
+    let path = |name: &str| dir.join(format!("{}.bin", name));
+    let mut v = vec![];
+    for &name in names {
+        match load(&path(name), name) {
+            Ok(data) => v.push(data),
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                let tables = build();
+                assert_eq!(tables.len(), names.len());
+                for (&name, data) in names.iter().zip(&tables) {
+                    save(&path(name), name, data)?;
+                }
+                return Ok(tables.into_iter().map(Data::Owned).collect());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(v)
+}
+
+/// The data must have the length.
+pub(crate) fn check_len(data: &[u8], len: usize) -> Result<(), Error> {
+    if data.len() != len {
+        return Err(Error::Format);
+    }
+    Ok(())
+}
+
+/// A table of `u16` in little endian.
+/// The values are read from the data in place so a mapped table isn't copied.
+pub(crate) struct U16s(Data);
+impl U16s {
+    pub(crate) fn new(v: &[u16]) -> Self {
+        U16s(Data::Owned(
+            v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        ))
+    }
+    pub(crate) fn from_data(data: Data) -> Self {
+        U16s(data)
+    }
+    pub(crate) fn get(&self, i: usize) -> u16 {
+        u16::from_le_bytes([self.0[2 * i], self.0[2 * i + 1]])
+    }
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let data: Vec<u8> = (0..100).collect();
+        let bytes = encode("test", &data);
+        assert_eq!((bytes.len() - data.len()) % 8, 0);
+        assert_eq!(decode(&bytes, "test").unwrap(), &data[..]);
+        assert!(matches!(decode(&bytes, "other"), Err(Error::Name(s)) if s == "test"));
+        assert!(matches!(decode(&bytes[..50], "test"), Err(Error::Format)));
+        assert!(matches!(decode(b"hello", "test"), Err(Error::Format)));
+
+        let mut broken = bytes.clone();
+        *broken.last_mut().unwrap() ^= 1;
+        assert!(matches!(decode(&broken, "test"), Err(Error::Checksum)));
+        let mut other = bytes.clone();
+        other[4] = 2;
+        assert!(matches!(decode(&other, "test"), Err(Error::Version(2))));
+        // Built with another definition of the moves.
+        let mut other = bytes.clone();
+        other[8] ^= 1;
+        assert!(matches!(decode(&other, "test"), Err(Error::Moves { .. })));
+    }
+    #[test]
+    fn test_load_or_build() {
+        let dir = std::env::temp_dir().join(format!("rubikmaster-table-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("table.bin");
+        let _ = fs::remove_file(&path);
+        let data = load_or_build(&path, "t", || vec![1, 2, 3]).unwrap();
+        assert_eq!(&*data, &[1, 2, 3]);
+        // Loaded without building.
+        let data = load_or_build(&path, "t", || unreachable!()).unwrap();
+        assert_eq!(&*data, &[1, 2, 3]);
+        assert!(matches!(load(&path, "u"), Err(Error::Name(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_load_or_build_all() {
+        let dir = std::env::temp_dir().join(format!("rubikmaster-tables-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let build = || vec![vec![1], U16s::new(&[2, 300]).bytes().to_vec()];
+        load_or_build_all(&dir, &["a", "b"], build).unwrap();
+        let mut v = load_or_build_all(&dir, &["a", "b"], || unreachable!()).unwrap();
+        assert_eq!(&*v[0], &[1]);
+        let b = U16s::from_data(v.pop().unwrap());
+        assert_eq!((b.get(0), b.get(1)), (2, 300));
+        // Rebuilt if one is missing.
+        fs::remove_file(dir.join("a.bin")).unwrap();
+        let v = load_or_build_all(&dir, &["a", "b"], build).unwrap();
+        assert_eq!(&*v[0], &[1]);
+        assert!(check_len(&v[1], 4).is_ok());
+        assert!(check_len(&v[1], 3).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
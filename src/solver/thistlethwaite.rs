@@ -0,0 +1,533 @@
+//! Thistlethwaite's four-phase algorithm.
+//!
+//! The cube is brought into a chain of subgroups one by one.
+//! - G1 = <L, R, F, B, U2, D2>: the edges are oriented.
+//! - G2 = <L, R, F2, B2, U2, D2>: the corners are oriented and the M-slice edges are in the M-slice.
+//! - G3 = <L2, R2, F2, B2, U2, D2>: the corners are in their tetrads and all edges are in their slices.
+//! - G4 = {I}: solved.
+//!
+//! Each phase is solved optimally within the moves of the previous subgroup
+//! by the exact distance tables of the cosets.
+//! The solution is longer than Kociemba's but every phase is easy to follow.
+
+use super::table::{self, Data, U16s};
+use super::{move_table, prune_table};
+use crate::cubie::{self, conjugate, perm_index, perm_of, CubieCube, Oriented, N_MOVE};
+use crate::matrix::PermutationMatrix;
+use crate::{Command, Move};
+use once_cell::sync::{Lazy, OnceCell};
+use std::convert::TryInto;
+use std::fmt;
+use std::path::Path;
+
+/// The subgroups in Thistlethwaite's algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subgroup {
+    G0,
+    G1,
+    G2,
+    G3,
+    G4,
+}
+impl Subgroup {
+    /// The generators. G4 has no generator.
+    pub fn generators(self) -> Vec<Command> {
+        use Move::*;
+        let faces = [L, R, F, B, U, D];
+        let quarter = match self {
+            Subgroup::G0 => 6,
+            Subgroup::G1 => 4,
+            Subgroup::G2 => 2,
+            Subgroup::G3 => 0,
+            Subgroup::G4 => return vec![],
+        };
+        faces
+            .iter()
+            .enumerate()
+            .map(|(i, &mov)| Command(mov, if i < quarter { 1 } else { 2 }))
+            .collect()
+    }
+    /// Check if the state is in the subgroup up to the whole cube rotation.
+    pub fn contains(self, m: &PermutationMatrix) -> bool {
+        match Oriented::new(m) {
+            Some(o) => self.contains_frame(&conjugate(&o.cube, *FRAME)),
+            None => false,
+        }
+    }
+    fn contains_frame(self, x: &CubieCube) -> bool {
+        match self {
+            Subgroup::G0 => x.is_solvable(),
+            Subgroup::G1 => x.flip() == 0,
+            Subgroup::G2 => x.flip() == 0 && tables().phase2.coord(x) == 0,
+            Subgroup::G3 => {
+                Subgroup::G2.contains_frame(x) && tables().phase3.coord(x) == tables().phase3.goal
+            }
+            Subgroup::G4 => x.is_solved(),
+        }
+    }
+}
+impl fmt::Display for Subgroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gens = self.generators();
+        if gens.is_empty() {
+            return write!(f, "{{I}}");
+        }
+        let v: Vec<String> = gens.iter().map(|c| c.to_string()).collect();
+        write!(f, "<{}>", v.join(", "))
+    }
+}
+
+/// Moves of a phase and the subgroup reached after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Phase {
+    pub moves: Vec<Command>,
+    pub subgroup: Subgroup,
+}
+
+/// Axis of the face in the order of `cubie::FACES`. U/D is 0, R/L is 1 and F/B is 2.
+fn axis(c: Command) -> usize {
+    cubie::index_of(c).unwrap() / 3 % 3
+}
+/// The rotation of the frame where the subgroups are solved.
+///
+/// In the frame, the edge orientation of `CubieCube` is flipped by F and B,
+/// and the E-slice edges are kept in the E-slice by U, D, R2, L2, F2 and B2.
+/// These are U, D and F, B in the original frame respectively.
+static FRAME: Lazy<usize> = Lazy::new(|| {
+    let c = |mov| Command(mov, 1);
+    (0..cubie::rotations().len())
+        .find(|&k| {
+            axis(cubie::relabel(c(Move::F), k)) == 0 && axis(cubie::relabel(c(Move::R), k)) == 2
+        })
+        .unwrap()
+});
+
+/// Moves of the subgroup in the frame.
+fn frame_moves(g: Subgroup) -> Vec<usize> {
+    (0..N_MOVE)
+        .filter(|&m| {
+            let half = m % 3 == 1;
+            let face = m / 3 % 3;
+            match g {
+                Subgroup::G0 => true,
+                // F and B
+                Subgroup::G1 => half || face != 2,
+                // U and D
+                Subgroup::G2 => half || face == 0,
+                Subgroup::G3 => half,
+                Subgroup::G4 => false,
+            }
+        })
+        .collect()
+}
+
+/// Exact distance table of a phase.
+struct PhaseTable {
+    get: fn(&Tables, &CubieCube) -> usize,
+    dist: Data,
+    goal: usize,
+}
+
+const M_EDGES: [u8; 4] = [1, 3, 5, 7];
+const S_EDGES: [u8; 4] = [0, 2, 4, 6];
+/// The M-slice edges at the home positions.
+const M_MASK: usize = 0b1010_1010;
+
+fn m_mask(x: &CubieCube) -> usize {
+    let mut mask = 0;
+    for i in 0..8 {
+        if M_EDGES.contains(&x.ep[i]) {
+            mask |= 1 << i;
+        }
+    }
+    mask
+}
+fn set_m_mask(x: &mut CubieCube, mask: usize) {
+    let (mut a, mut b) = (0, 0);
+    for i in 0..8 {
+        if mask & (1 << i) != 0 && a < 4 {
+            x.ep[i] = M_EDGES[a];
+            a += 1;
+        } else {
+            x.ep[i] = S_EDGES[b % 4];
+            b += 1;
+        }
+    }
+}
+
+struct Tables {
+    phase1: PhaseTable,
+    phase2: PhaseTable,
+    phase3: PhaseTable,
+    phase4: PhaseTable,
+    /// Right coset of the corner permutations in G3 for each corner permutation.
+    coset: U16s,
+    /// Index of the corner permutations in G3.
+    h_index: Data,
+}
+impl PhaseTable {
+    fn coord(&self, x: &CubieCube) -> usize {
+        (self.get)(tables(), x)
+    }
+}
+impl Tables {
+    fn new() -> Self {
+        // Phase 1: the edge orientation.
+        let all = frame_moves(Subgroup::G0);
+        let flip_move = move_table(2048, &all, CubieCube::set_flip, CubieCube::flip);
+        let phase1 = prune_table(2048, 0, &all, |x, m| flip_move[x * N_MOVE + m] as usize);
+
+        // Phase 2: the corner orientation and the positions of the E-slice edges in the frame.
+        let g1 = frame_moves(Subgroup::G1);
+        let twist_move = move_table(2187, &g1, CubieCube::set_twist, CubieCube::twist);
+        let slice_move = move_table(
+            495,
+            &g1,
+            |x, s| x.set_slice_sorted(24 * s),
+            CubieCube::slice,
+        );
+        let phase2 = prune_table(2187 * 495, 0, &g1, |x, m| {
+            let (t, s) = (x / 495, x % 495);
+            twist_move[t * N_MOVE + m] as usize * 495 + slice_move[s * N_MOVE + m] as usize
+        });
+
+        // The corner permutations reachable by half turns.
+        let g3 = frame_moves(Subgroup::G3);
+        let mut h = vec![CubieCube::identity()];
+        let mut k = 0;
+        while k < h.len() {
+            for &m in &g3 {
+                let y = h[k].apply(m);
+                if h.iter().all(|x| x.cp != y.cp) {
+                    h.push(y);
+                }
+            }
+            k += 1;
+        }
+        let mut h_index = vec![u8::MAX; 40320];
+        for (i, x) in h.iter().enumerate() {
+            h_index[x.corners()] = i as u8;
+        }
+        let mut coset = vec![u16::MAX; 40320];
+        let mut n_coset = 0;
+        for p in 0..40320 {
+            if coset[p] != u16::MAX {
+                continue;
+            }
+            let mut x = CubieCube::identity();
+            x.set_corners(p);
+            for y in &h {
+                coset[y.multiply(&x).corners()] = n_coset;
+            }
+            n_coset += 1;
+        }
+        let mut reps = vec![0; n_coset as usize];
+        for p in (0..40320).rev() {
+            reps[coset[p] as usize] = p;
+        }
+
+        // Phase 3: the coset of the corner permutation and the positions of the M-slice edges.
+        let g2 = frame_moves(Subgroup::G2);
+        let coset_move = move_table(
+            n_coset as usize,
+            &g2,
+            |x, c| x.set_corners(reps[c]),
+            |x| coset[x.corners()] as usize,
+        );
+        let mask_move = move_table(256, &g2, set_m_mask, m_mask);
+        let goal = coset[0] as usize * 256 + M_MASK;
+        let phase3 = prune_table(n_coset as usize * 256, goal, &g2, |x, m| {
+            let (c, e) = (x / 256, x % 256);
+            coset_move[c * N_MOVE + m] as usize * 256 + mask_move[e * N_MOVE + m] as usize
+        });
+
+        // Phase 4: the corner permutation in G3 and the permutation in each slice.
+        let corner_move = move_table(
+            h.len(),
+            &g3,
+            |x, i| *x = h[i],
+            |x| h_index[x.corners()] as usize,
+        );
+        let edge_move = move_table(13824, &g3, set_slice_perms, slice_perms);
+        let phase4 = prune_table(h.len() * 13824, 0, &g3, |x, m| {
+            let (c, e) = (x / 13824, x % 13824);
+            corner_move[c * N_MOVE + m] as usize * 13824 + edge_move[e * N_MOVE + m] as usize
+        });
+
+        Self::assemble(
+            [phase1, phase2, phase3, phase4].map(Data::Owned),
+            U16s::new(&coset),
+            Data::Owned(h_index),
+        )
+    }
+    fn assemble(dist: [Data; 4], coset: U16s, h_index: Data) -> Self {
+        let [phase1, phase2, phase3, phase4] = dist;
+        Self {
+            phase1: PhaseTable {
+                get: |_, x| x.flip(),
+                dist: phase1,
+                goal: 0,
+            },
+            phase2: PhaseTable {
+                get: |_, x| x.twist() * 495 + x.slice(),
+                dist: phase2,
+                goal: 0,
+            },
+            phase3: PhaseTable {
+                get: |t, x| t.coset.get(x.corners()) as usize * 256 + m_mask(x),
+                dist: phase3,
+                goal: coset.get(0) as usize * 256 + M_MASK,
+            },
+            phase4: PhaseTable {
+                get: |t, x| t.h_index[x.corners()] as usize * 13824 + slice_perms(x),
+                dist: phase4,
+                goal: 0,
+            },
+            coset,
+            h_index,
+        }
+    }
+    fn to_bytes(&self) -> Vec<Vec<u8>> {
+        vec![
+            self.phase1.dist.to_vec(),
+            self.phase2.dist.to_vec(),
+            self.phase3.dist.to_vec(),
+            self.phase4.dist.to_vec(),
+            self.coset.bytes().to_vec(),
+            self.h_index.to_vec(),
+        ]
+    }
+    /// The tables in the order of `to_bytes`. The data is used in place.
+    fn from_data(data: Vec<Data>) -> Result<Self, table::Error> {
+        let [phase1, phase2, phase3, phase4, coset, h_index]: [Data; 6] =
+            data.try_into().map_err(|_| table::Error::Format)?;
+        table::check_len(&coset, 2 * 40320)?;
+        table::check_len(&h_index, 40320)?;
+        let coset = U16s::from_data(coset);
+        let n_coset = (0..40320).map(|p| coset.get(p)).max().unwrap() as usize + 1;
+        let n_h = h_index.iter().filter(|&&i| i != u8::MAX).count();
+        let dist = [phase1, phase2, phase3, phase4];
+        let lens = [2048, 2187 * 495, n_coset * 256, n_h * 13824];
+        for (d, &len) in dist.iter().zip(&lens) {
+            table::check_len(d, len)?;
+        }
+        Ok(Self::assemble(dist, coset, h_index))
+    }
+}
+/// The permutations of the edges in the M, S and E slices.
+fn slice_perms(x: &CubieCube) -> usize {
+    let mut v = 0;
+    for slice in [M_EDGES, S_EDGES, [8, 9, 10, 11]] {
+        let mut p = [0u8; 4];
+        for (k, &i) in slice.iter().enumerate() {
+            p[k] = slice
+                .iter()
+                .position(|&e| e == x.ep[i as usize])
+                .unwrap_or(0) as u8;
+        }
+        v = v * 24 + perm_index(&p);
+    }
+    v
+}
+fn set_slice_perms(x: &mut CubieCube, mut idx: usize) {
+    for slice in [[8, 9, 10, 11], S_EDGES, M_EDGES] {
+        let mut p = [0u8; 4];
+        perm_of(idx % 24, &mut p);
+        idx /= 24;
+        for (k, &i) in slice.iter().enumerate() {
+            x.ep[i as usize] = slice[p[k] as usize];
+        }
+    }
+}
+static TABLES: OnceCell<Tables> = OnceCell::new();
+const TABLE_NAMES: [&str; 6] = [
+    "thistlethwaite-phase1",
+    "thistlethwaite-phase2",
+    "thistlethwaite-phase3",
+    "thistlethwaite-phase4",
+    "thistlethwaite-coset",
+    "thistlethwaite-h-index",
+];
+
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(Tables::new)
+}
+
+/// Generate the tables in advance. Otherwise they are generated on the first solve.
+pub fn init() {
+    tables();
+}
+
+/// `init` with the tables saved in the directory.
+///
+/// The tables are generated and saved only if the files don't exist.
+/// Nothing is done if the tables are already generated.
+pub fn init_from(dir: &Path) -> Result<(), table::Error> {
+    if TABLES.get().is_some() {
+        return Ok(());
+    }
+    let data = table::load_or_build_all(dir, &TABLE_NAMES, || Tables::new().to_bytes())?;
+    let _ = TABLES.set(Tables::from_data(data)?);
+    Ok(())
+}
+
+/// Solve a phase by following the distance table.
+fn solve_phase(x: &mut CubieCube, table: &PhaseTable, moves: &[usize]) -> Vec<usize> {
+    let mut v = vec![];
+    let mut d = table.dist[table.coord(x)];
+    while d > 0 {
+        let m = moves
+            .iter()
+            .copied()
+            .find(|&m| table.dist[table.coord(&x.apply(m))] < d)
+            .unwrap();
+        *x = x.apply(m);
+        d -= 1;
+        v.push(m);
+    }
+    v
+}
+
+/// Solve the state and return the moves split by phase.
+///
+/// `None` if the state is broken.
+/// The solution is solved up to the whole cube rotation.
+/// If the state has the centers moved, the subgroups are seen from the centers.
+pub fn solve(m: &PermutationMatrix) -> Option<Vec<Phase>> {
+    let o = Oriented::new(m)?;
+    if !o.cube.is_solvable() {
+        return None;
+    }
+    let t = tables();
+    let frame = *FRAME;
+    let mut x = conjugate(&o.cube, frame);
+    let phases = [
+        (&t.phase1, Subgroup::G0, Subgroup::G1),
+        (&t.phase2, Subgroup::G1, Subgroup::G2),
+        (&t.phase3, Subgroup::G2, Subgroup::G3),
+        (&t.phase4, Subgroup::G3, Subgroup::G4),
+    ];
+    let mut v = vec![];
+    for (table, from, to) in phases {
+        let moves = solve_phase(&mut x, table, &frame_moves(from));
+        let moves: Vec<Command> = moves
+            .into_iter()
+            .map(|m| cubie::relabel(cubie::command_of(m), frame))
+            .collect();
+        v.push(Phase {
+            moves: o.relabel(&moves),
+            subgroup: to,
+        });
+    }
+    Some(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfop::solved;
+    use crate::matrix;
+
+    const SUBGROUPS: [Subgroup; 5] = [
+        Subgroup::G0,
+        Subgroup::G1,
+        Subgroup::G2,
+        Subgroup::G3,
+        Subgroup::G4,
+    ];
+
+    fn random_in(g: Subgroup, n: usize) -> Vec<Command> {
+        let gens = g.generators();
+        (0..n)
+            .map(|_| {
+                let c = gens[rand::random::<usize>() % gens.len()];
+                if rand::random() {
+                    c.prime()
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+    #[test]
+    fn test_subgroup() {
+        assert_eq!(Subgroup::G1.to_string(), "<L, R, F, B, U2, D2>");
+        assert_eq!(Subgroup::G4.to_string(), "{I}");
+        for (k, &g) in SUBGROUPS[..4].iter().enumerate() {
+            for _ in 0..20 {
+                let m = matrix::of_seq(&random_in(g, 30));
+                for (j, &h) in SUBGROUPS.iter().enumerate() {
+                    if j <= k {
+                        assert!(h.contains(&m));
+                    }
+                }
+            }
+        }
+        let u = matrix::of_seq(&[Command(Move::U, 1)]);
+        assert!(!Subgroup::G1.contains(&u));
+        let f = matrix::of_seq(&[Command(Move::F, 1)]);
+        assert!(Subgroup::G1.contains(&f));
+        assert!(!Subgroup::G2.contains(&f));
+        let r = matrix::of_seq(&[Command(Move::R, 1)]);
+        assert!(Subgroup::G2.contains(&r));
+        assert!(!Subgroup::G3.contains(&r));
+    }
+    #[test]
+    fn test_tables() {
+        let t = tables();
+        assert_eq!(t.phase3.dist.len(), 420 * 256);
+        let max = |p: &PhaseTable| p.dist.iter().filter(|&&d| d != u8::MAX).max().copied();
+        assert_eq!(max(&t.phase1), Some(7));
+        assert_eq!(max(&t.phase2), Some(10));
+        assert_eq!(max(&t.phase3), Some(13));
+        assert_eq!(max(&t.phase4), Some(15));
+    }
+    #[test]
+    fn test_save() {
+        let dir =
+            std::env::temp_dir().join(format!("rubikmaster-thistlethwaite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = tables().to_bytes();
+        table::load_or_build_all(&dir, &TABLE_NAMES, || bytes.clone()).unwrap();
+        let data = table::load_or_build_all(&dir, &TABLE_NAMES, || unreachable!()).unwrap();
+        let t = Tables::from_data(data).unwrap();
+        assert_eq!(t.to_bytes(), bytes);
+        assert_eq!(t.phase3.goal, tables().phase3.goal);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_solve() {
+        for _ in 0..10 {
+            let scramble = random_in(Subgroup::G0, 30);
+            let phases = solve(&matrix::of_seq(&scramble)).unwrap();
+            assert_eq!(phases.len(), 4);
+            let mut seq = scramble.clone();
+            let mut from = Subgroup::G0;
+            for phase in &phases {
+                let gens = from.generators();
+                for c in &phase.moves {
+                    assert!(gens
+                        .iter()
+                        .any(|g| g.0 == c.0 && (g.1 == 1 || c.1.abs() == 2)));
+                }
+                seq.extend(&phase.moves);
+                assert!(phase.subgroup.contains(&matrix::of_seq(&seq)));
+                from = phase.subgroup;
+            }
+            assert!(solved(&matrix::of_seq(&seq)));
+            let n: usize = phases.iter().map(|p| p.moves.len()).sum();
+            assert!(n <= 52);
+        }
+        // Slice moves and rotations in the scramble.
+        for _ in 0..10 {
+            let scramble = crate::random(30);
+            let phases = solve(&matrix::of_seq(&scramble)).unwrap();
+            let mut seq = scramble.clone();
+            for phase in &phases {
+                seq.extend(&phase.moves);
+                assert!(phase.subgroup.contains(&matrix::of_seq(&seq)));
+            }
+            assert!(solved(&matrix::of_seq(&seq)));
+        }
+    }
+}
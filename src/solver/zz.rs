@@ -0,0 +1,278 @@
+//! ZZ solver.
+//!
+//! EOLine is solved optimally by a table over the edge orientation and the positions of DF and DB.
+//! EOCross is searched by IDA* with the tables of DF and DB and of DR and DL.
+//! The F2L is solved block by block with <R, U, L> so the orientation is kept.
+//!
+//! Like the cross solver, the steps are solved for the colors of the centers.
+
+use super::block::{block, BlockSearch};
+use super::table::{self, Data};
+use super::{follows, move_table, prune_table, Progress, Solution};
+use crate::cfop::f2l_solved;
+use crate::cubie::{self, CubieCube, Oriented, N_MOVE};
+use crate::matrix::PermutationMatrix;
+use crate::zz::eoline_solved;
+use crate::{Command, Move};
+use once_cell::sync::{Lazy, OnceCell};
+use std::path::Path;
+
+const N_FLIP: usize = 2048;
+/// The positions of two edges.
+const N_PAIR: usize = 144;
+const DR: usize = 4;
+const DF: usize = 5;
+const DL: usize = 6;
+const DB: usize = 7;
+/// No block needs more moves.
+const MAX_DEPTH: usize = 20;
+
+static FLIP_MOVE: Lazy<Vec<u16>> = Lazy::new(|| {
+    let moves: Vec<usize> = (0..N_MOVE).collect();
+    move_table(N_FLIP, &moves, |x, i| x.set_flip(i), |x| x.flip())
+});
+/// The edge at the position `p` moves to `EDGE_DEST[m][p]` by the move `m`.
+static EDGE_DEST: Lazy<Vec<[u8; 12]>> = Lazy::new(|| {
+    (0..N_MOVE)
+        .map(|m| {
+            let x = CubieCube::identity().apply(m);
+            let mut dest = [0; 12];
+            for (i, &p) in x.ep.iter().enumerate() {
+                dest[p as usize] = i as u8;
+            }
+            dest
+        })
+        .collect()
+});
+
+/// Exact distances to orient the edges and solve two edges.
+struct LineTable {
+    edges: [usize; 2],
+    dist: Data,
+}
+impl LineTable {
+    fn new(edges: [usize; 2]) -> Self {
+        let moves: Vec<usize> = (0..N_MOVE).collect();
+        let dist = prune_table(N_FLIP * N_PAIR, Self::index(0, edges), &moves, |x, m| {
+            Self::next(x, m)
+        });
+        Self {
+            edges,
+            dist: Data::Owned(dist),
+        }
+    }
+    /// The data is used in place.
+    fn from_data(edges: [usize; 2], data: Data) -> Result<Self, table::Error> {
+        table::check_len(&data, N_FLIP * N_PAIR)?;
+        Ok(Self { edges, dist: data })
+    }
+    fn index(flip: usize, pos: [usize; 2]) -> usize {
+        flip * N_PAIR + pos[0] * 12 + pos[1]
+    }
+    fn next(x: usize, m: usize) -> usize {
+        let (flip, a, b) = (x / N_PAIR, x % N_PAIR / 12, x % 12);
+        let dest = &EDGE_DEST[m];
+        Self::index(
+            FLIP_MOVE[flip * N_MOVE + m] as usize,
+            [dest[a] as usize, dest[b] as usize],
+        )
+    }
+    fn coord(&self, x: &CubieCube) -> usize {
+        let pos = self
+            .edges
+            .map(|e| x.ep.iter().position(|&p| p as usize == e).unwrap());
+        Self::index(x.flip(), pos)
+    }
+}
+/// DF and DB, and DR and DL.
+static TABLES: OnceCell<[LineTable; 2]> = OnceCell::new();
+const TABLE_NAMES: [&str; 2] = ["zz-line", "zz-sides"];
+
+fn tables() -> &'static [LineTable; 2] {
+    TABLES.get_or_init(|| [LineTable::new([DF, DB]), LineTable::new([DR, DL])])
+}
+
+/// Generate the tables in advance. Otherwise they are generated on the first solve.
+pub fn init() {
+    tables();
+}
+
+/// `init` with the tables saved in the directory.
+///
+/// The tables are generated and saved only if the files don't exist.
+/// Nothing is done if the tables are already generated.
+pub fn init_from(dir: &Path) -> Result<(), table::Error> {
+    if TABLES.get().is_some() {
+        return Ok(());
+    }
+    let mut data = table::load_or_build_all(dir, &TABLE_NAMES, || {
+        vec![
+            LineTable::new([DF, DB]).dist.to_vec(),
+            LineTable::new([DR, DL]).dist.to_vec(),
+        ]
+    })?;
+    let sides = LineTable::from_data([DR, DL], data.pop().unwrap())?;
+    let line = LineTable::from_data([DF, DB], data.pop().unwrap())?;
+    let _ = TABLES.set([line, sides]);
+    Ok(())
+}
+
+/// An optimal EOLine solution. `None` if the state is broken.
+pub fn eoline(m: &PermutationMatrix) -> Option<Vec<Command>> {
+    let o = Oriented::new(m)?;
+    let [line, _] = tables();
+    let mut x = line.coord(&o.cube);
+    let mut path = vec![];
+    while line.dist[x] > 0 {
+        let d = line.dist[x];
+        let k = (0..N_MOVE)
+            .find(|&k| line.dist[LineTable::next(x, k)] < d)
+            .unwrap();
+        x = LineTable::next(x, k);
+        path.push(cubie::command_of(k));
+    }
+    Some(o.relabel(&path))
+}
+
+fn eocross_dfs(x: [usize; 2], togo: usize, path: &mut Vec<usize>) -> bool {
+    let [line, sides] = tables();
+    let h = line.dist[x[0]].max(sides.dist[x[1]]) as usize;
+    if h == 0 {
+        return true;
+    }
+    if h > togo {
+        return false;
+    }
+    for k in 0..N_MOVE {
+        if !follows(path.last().copied(), k) {
+            continue;
+        }
+        path.push(k);
+        let y = [LineTable::next(x[0], k), LineTable::next(x[1], k)];
+        if eocross_dfs(y, togo - 1, path) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+/// An optimal EOCross solution. `None` if the state is broken.
+pub fn eocross(m: &PermutationMatrix) -> Option<Vec<Command>> {
+    let o = Oriented::new(m)?;
+    let [line, sides] = tables();
+    let x = [line.coord(&o.cube), sides.coord(&o.cube)];
+    let mut path = vec![];
+    let mut depth = 0;
+    while !eocross_dfs(x, depth, &mut path) {
+        depth += 1;
+    }
+    let cs: Vec<Command> = path.into_iter().map(cubie::command_of).collect();
+    Some(o.relabel(&cs))
+}
+
+static LEFT_BLOCK: Lazy<BlockSearch> =
+    Lazy::new(|| block([5, 6], [DL, 9, 10], &[Move::L, Move::U, Move::R]));
+static RIGHT_BLOCK: Lazy<BlockSearch> =
+    Lazy::new(|| block([4, 7], [DR, 8, 11], &[Move::R, Move::U]));
+
+/// Solve the F2L after the EOLine with <R, U, L>.
+///
+/// The left block is solved with <R, U, L> and then the right block with <R, U>.
+/// `None` if the EOLine is not solved.
+pub fn f2l(m: &PermutationMatrix) -> Option<Solution> {
+    let o = Oriented::new(m)?;
+    let x = o.cube.to_matrix();
+    if !eoline_solved(&x) {
+        return None;
+    }
+    let mut s = Progress::new(&x);
+    let moves = LEFT_BLOCK.solve(&s.m, MAX_DEPTH)?;
+    s.push("Left block", moves);
+    let moves = RIGHT_BLOCK.solve(&s.m, MAX_DEPTH)?;
+    s.push("Right block", moves);
+    if !f2l_solved(&s.m) {
+        return None;
+    }
+    let mut sol = s.finish();
+    for step in &mut sol.steps {
+        step.moves = o.relabel(&step.moves);
+    }
+    Some(sol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix;
+    use crate::zz::eocross_solved;
+
+    /// Rotate the whole cube to bring the centers home.
+    fn home(m: &PermutationMatrix) -> PermutationMatrix {
+        let o = Oriented::new(m).unwrap();
+        matrix::of_seq(&cubie::rotations()[o.rotation]) * *m
+    }
+    #[test]
+    fn test_tables() {
+        let [line, _] = tables();
+        assert_eq!(line.dist.iter().filter(|&&d| d == 0).count(), 1);
+        // EOLine is known to be at most 9 moves.
+        let max = line.dist.iter().filter(|&&d| d != u8::MAX).max();
+        assert_eq!(max, Some(&9));
+    }
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("rubikmaster-zz-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let [line, sides] = tables();
+        let bytes = vec![line.dist.to_vec(), sides.dist.to_vec()];
+        table::load_or_build_all(&dir, &TABLE_NAMES, || bytes.clone()).unwrap();
+        let mut data = table::load_or_build_all(&dir, &TABLE_NAMES, || unreachable!()).unwrap();
+        let t = LineTable::from_data([DR, DL], data.pop().unwrap()).unwrap();
+        assert_eq!(&*t.dist, &*sides.dist);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_eoline() {
+        for _ in 0..20 {
+            let scramble = crate::random(25);
+            let m = matrix::of_seq(&scramble);
+            let sol = eoline(&m).unwrap();
+            assert!(sol.len() <= 9);
+            assert!(eoline_solved(&home(&(matrix::of_seq(&sol) * m))));
+        }
+    }
+    #[test]
+    fn test_eocross() {
+        for _ in 0..5 {
+            let m = matrix::of_seq(&crate::random(25));
+            let sol = eocross(&m).unwrap();
+            assert!(sol.len() >= eoline(&m).unwrap().len());
+            assert!(eocross_solved(&home(&(matrix::of_seq(&sol) * m))));
+        }
+    }
+    #[test]
+    fn test_f2l() {
+        assert!(f2l(&matrix::of_seq(&[Command(Move::F, 1)])).is_none());
+        for _ in 0..20 {
+            let m = matrix::of_seq(&crate::random(25));
+            let mut seq = eoline(&m).unwrap();
+            let x = matrix::of_seq(&seq) * m;
+            let sol = f2l(&x).unwrap();
+            assert_eq!(sol.steps.len(), 2);
+            let moves = sol.moves();
+            // The moves seen from the centers.
+            let rot = Oriented::new(&x).unwrap().rotation;
+            let unlabel = |c| {
+                (0..N_MOVE)
+                    .map(cubie::command_of)
+                    .find(|&d| cubie::relabel(d, rot) == c)
+                    .unwrap()
+            };
+            assert!(moves
+                .iter()
+                .all(|&c| matches!(unlabel(c).0, Move::R | Move::U | Move::L)));
+            seq.extend(moves);
+            assert!(f2l_solved(&home(&(matrix::of_seq(&seq) * m))));
+        }
+    }
+}
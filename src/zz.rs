@@ -0,0 +1,136 @@
+//! Collection of ZZ related tools.
+//!
+//! The edge orientation is relative to the centers.
+//! An edge is bad on an axis if it can't be solved without the quarter turns of the faces on the axis.
+
+use crate::cfop::cross_solved;
+use crate::coord::Axis;
+use crate::cubie::edge_facelets;
+use crate::matrix::{same_color_check, PermutationMatrix};
+
+/// The axis of the face: R and L are on x, U and D on y and F and B on z.
+fn axis_of(face: u8) -> Axis {
+    match face / 2 {
+        0 => Axis::X,
+        1 => Axis::Y,
+        _ => Axis::Z,
+    }
+}
+/// The axis whose stickers are looked at first.
+fn primary(axis: Axis) -> Axis {
+    match axis {
+        Axis::Y => Axis::Z,
+        _ => Axis::Y,
+    }
+}
+
+/// Positions of the bad edges in the order of `cubie`.
+pub fn bad_edges(mat: &PermutationMatrix, axis: Axis) -> Vec<usize> {
+    // The face of each color by the centers.
+    let mut face_of = [0; 6];
+    for f in 0..6 {
+        face_of[mat.inv_perm[9 * f + 4] as usize / 9] = f as u8;
+    }
+    let p = primary(axis);
+    (0..12)
+        .filter(|&i| {
+            let fs = edge_facelets(i);
+            let colors = fs.map(|f| face_of[mat.inv_perm[f as usize] as usize / 9]);
+            // The sticker of the piece to look at and the facelet of the position to look at.
+            let piece = if colors.iter().any(|&c| axis_of(c) == p) {
+                colors.iter().position(|&c| axis_of(c) == p).unwrap()
+            } else {
+                colors.iter().position(|&c| axis_of(c) == axis).unwrap()
+            };
+            let pos = if fs.iter().any(|&f| axis_of(f / 9) == p) {
+                fs.iter().position(|&f| axis_of(f / 9) == p).unwrap()
+            } else {
+                fs.iter().position(|&f| axis_of(f / 9) == axis).unwrap()
+            };
+            piece != pos
+        })
+        .collect()
+}
+/// Check if all edges are good on the axis.
+pub fn eo_solved(mat: &PermutationMatrix, axis: Axis) -> bool {
+    bad_edges(mat, axis).is_empty()
+}
+
+/// Check if the edges are oriented on the F/B axis and the DF and DB edges are solved.
+pub fn eoline_solved(mat: &PermutationMatrix) -> bool {
+    eo_solved(mat, Axis::Z)
+        && same_color_check(mat, [28, 31, 34])
+        && same_color_check(mat, [43, 40])
+        && same_color_check(mat, [48, 49])
+}
+/// Check if the edges are oriented on the F/B axis and the cross on D is solved.
+pub fn eocross_solved(mat: &PermutationMatrix) -> bool {
+    eo_solved(mat, Axis::Z) && cross_solved(mat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix;
+    use crate::parser::parse;
+    use crate::{flatten, Command, Move};
+
+    fn of(s: &str) -> PermutationMatrix {
+        matrix::of_seq(&flatten(parse(s).unwrap().1))
+    }
+    #[test]
+    fn test_bad_edges() {
+        // F flips UF, FR, DF and FL.
+        assert_eq!(bad_edges(&of("F"), Axis::Z), vec![1, 5, 8, 9]);
+        assert!(eo_solved(&of("F2"), Axis::Z));
+        assert!(eo_solved(&of("F"), Axis::X) && eo_solved(&of("F"), Axis::Y));
+        assert_eq!(bad_edges(&of("U"), Axis::Y), vec![0, 1, 2, 3]);
+        assert_eq!(bad_edges(&of("R'"), Axis::X), vec![0, 4, 8, 11]);
+        // Half turns and the turns on the other axes keep the orientation.
+        for _ in 0..20 {
+            let cs: Vec<Command> = crate::random(30)
+                .into_iter()
+                .filter(|c| {
+                    matches!(c.0, Move::R | Move::L | Move::U | Move::D)
+                        || matches!(c.0, Move::F | Move::B) && c.1 % 2 == 0
+                })
+                .collect();
+            assert!(eo_solved(&matrix::of_seq(&cs), Axis::Z));
+        }
+        // The orientation is relative to the centers.
+        for s in ["x", "y", "z", "x'y2"] {
+            let m = of(s);
+            assert!(eo_solved(&m, Axis::X) && eo_solved(&m, Axis::Y) && eo_solved(&m, Axis::Z));
+        }
+        // A quarter turn of M is R L' with a rotation.
+        assert!(eo_solved(&of("M"), Axis::Z));
+        assert_eq!(bad_edges(&of("M"), Axis::X).len(), 8);
+        // The F/B axis is the orientation of `cubie`.
+        for _ in 0..20 {
+            let cs: Vec<Command> = (0..20)
+                .map(|_| crate::cubie::command_of(rand::random::<usize>() % 18))
+                .collect();
+            let m = matrix::of_seq(&cs);
+            let x = crate::cubie::CubieCube::from_matrix(&m).unwrap();
+            let flipped: Vec<usize> = (0..12).filter(|&i| x.eo[i] == 1).collect();
+            assert_eq!(bad_edges(&m, Axis::Z), flipped);
+        }
+        // The bad edges on a random state are even.
+        for _ in 0..100 {
+            let m = matrix::of_seq(&crate::random(20));
+            for axis in [Axis::X, Axis::Y, Axis::Z] {
+                assert_eq!(bad_edges(&m, axis).len() % 2, 0);
+            }
+        }
+    }
+    #[test]
+    fn test_eoline() {
+        let m = PermutationMatrix::identity();
+        assert!(eoline_solved(&m) && eocross_solved(&m));
+        let m = matrix::of_seq(&[Command(Move::R, 1), Command(Move::U, 1)]);
+        assert!(eoline_solved(&m));
+        assert!(!eocross_solved(&m));
+        assert!(!eoline_solved(&of("F2")));
+        assert!(!eoline_solved(&of("B")));
+    }
+}